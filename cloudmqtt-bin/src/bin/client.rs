@@ -10,8 +10,8 @@
 use cloudmqtt::client::connect::MqttClientConnector;
 use cloudmqtt::client::send::Publish;
 use cloudmqtt::client::MqttClient;
+use cloudmqtt::transport::AddressFamilyPreference;
 use cloudmqtt::transport::MqttConnectTransport;
-use tokio::net::TcpStream;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
@@ -39,9 +39,9 @@ async fn main() {
         .with(fmt_layer)
         .init();
 
-    let socket = TcpStream::connect(args.hostname).await.unwrap();
-
-    let connection = MqttConnectTransport::TokioTcp(socket);
+    let connection = MqttConnectTransport::connect_tcp(args.hostname, AddressFamilyPreference::Any)
+        .await
+        .unwrap();
     let client_id =
         cloudmqtt::client_identifier::ProposedClientIdentifier::PotentiallyServerProvided;
 
@@ -76,7 +76,8 @@ async fn main() {
         .await
         .unwrap()
         .acknowledged()
-        .await;
+        .await
+        .unwrap();
 
     client.ping().await.unwrap().response().await;
 
@@ -95,5 +96,10 @@ async fn main() {
 
     tokio::time::sleep(Duration::from_secs(20)).await;
 
+    client
+        .disconnect(cloudmqtt::packets::disconnect::DisconnectReasonCode::NormalDisconnection)
+        .await
+        .unwrap();
+
     println!("Sent message! Bye");
 }
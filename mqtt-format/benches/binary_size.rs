@@ -0,0 +1,92 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Compares recomputing the binary size of a property heavy PUBLISH with using the size cached
+//! by `MqttPacket::into_sized`
+//!
+//! Run with `cargo bench -p mqtt-format --bench binary_size`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use mqtt_format::v5::packets::publish::MPublish;
+use mqtt_format::v5::packets::publish::PublishProperties;
+use mqtt_format::v5::packets::MqttPacket;
+use mqtt_format::v5::qos::QualityOfService;
+use mqtt_format::v5::variable_header::ContentType;
+use mqtt_format::v5::variable_header::CorrelationData;
+use mqtt_format::v5::variable_header::MessageExpiryInterval;
+use mqtt_format::v5::variable_header::PayloadFormatIndicator;
+use mqtt_format::v5::variable_header::ResponseTopic;
+use mqtt_format::v5::variable_header::SubscriptionIdentifier;
+use mqtt_format::v5::variable_header::UserProperties;
+
+const ITERATIONS: u32 = 100_000;
+
+/// How often the size is needed when publishing: size check, logging, buffer reservation and
+/// encoding
+const SIZE_LOOKUPS: u32 = 4;
+
+fn user_properties(count: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    for i in 0..count {
+        if i != 0 {
+            buffer.push(0x26);
+        }
+        let key = format!("key-{i}");
+        let value = format!("value-{i}");
+        buffer.extend_from_slice(&(key.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(key.as_bytes());
+        buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(value.as_bytes());
+    }
+    buffer
+}
+
+fn bench(name: &str, mut f: impl FnMut() -> u32) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    let elapsed = start.elapsed();
+
+    println!("{name:>12}: {:?} per iteration", elapsed / ITERATIONS);
+}
+
+fn main() {
+    let user_properties = user_properties(64);
+    let payload = vec![0xAB; 4096];
+
+    let packet = MqttPacket::Publish(MPublish {
+        duplicate: false,
+        quality_of_service: QualityOfService::AtLeastOnce,
+        retain: false,
+        topic_name: "sensors/building-1/floor-2/temperature",
+        packet_identifier: Some(mqtt_format::v5::variable_header::PacketIdentifier(
+            core::num::NonZeroU16::MIN,
+        )),
+        properties: PublishProperties {
+            payload_format_indicator: Some(PayloadFormatIndicator(1)),
+            message_expiry_interval: Some(MessageExpiryInterval(3600)),
+            topic_alias: None,
+            response_topic: Some(ResponseTopic("sensors/building-1/responses")),
+            correlation_data: Some(CorrelationData(&[0x42; 16])),
+            user_properties: Some(UserProperties(&user_properties)),
+            subscription_identifier: Some(SubscriptionIdentifier(12)),
+            content_type: Some(ContentType("application/json")),
+        },
+        payload: &payload,
+    });
+
+    bench("recomputed", || {
+        (0..SIZE_LOOKUPS).fold(0, |acc, _| acc ^ black_box(&packet).binary_size())
+    });
+
+    bench("cached", || {
+        let sized = black_box(&packet).clone().into_sized();
+        (0..SIZE_LOOKUPS).fold(0, |acc, _| acc ^ black_box(&sized).encoded_size())
+    });
+}
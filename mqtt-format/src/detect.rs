@@ -0,0 +1,167 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+//! Detecting the protocol version of a connection from its CONNECT packet
+
+/// A CONNECT packet of either supported protocol version
+#[derive(Debug, PartialEq)]
+pub enum EitherConnect<'i> {
+    V3(crate::v3::packet::MConnect<'i>),
+    V5(crate::v5::packets::connect::MConnect<'i>),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DetectConnectError<'i> {
+    #[error("The packet is not a CONNECT packet")]
+    NotAConnect,
+
+    #[error("The packet ended before the protocol level")]
+    Incomplete,
+
+    #[error("The client sent an unsupported protocol level: {}", .0)]
+    UnsupportedProtocolLevel(u8),
+
+    #[error("Could not parse the MQTTv3 CONNECT packet")]
+    V3(nom::Err<nom::error::Error<&'i [u8]>>),
+
+    #[error("Could not parse the MQTTv5 CONNECT packet")]
+    V5(winnow::error::ErrMode<winnow::error::ContextError>),
+}
+
+const PROTOCOL_LEVEL_V3: u8 = 4;
+const PROTOCOL_LEVEL_V5: u8 = 5;
+
+/// Find the protocol level of a complete CONNECT packet, without parsing the rest of it
+fn peek_protocol_level(input: &[u8]) -> Result<u8, DetectConnectError<'_>> {
+    let (&first_byte, rest) = input.split_first().ok_or(DetectConnectError::Incomplete)?;
+    if first_byte >> 4 != 1 {
+        return Err(DetectConnectError::NotAConnect);
+    }
+
+    // The remaining length is a variable byte integer of at most four bytes
+    let length_bytes = rest
+        .iter()
+        .take(4)
+        .position(|byte| byte & 0x80 == 0)
+        .ok_or(DetectConnectError::Incomplete)?
+        + 1;
+    let rest = &rest[length_bytes..];
+
+    let name_length = match rest {
+        [high, low, ..] => u16::from_be_bytes([*high, *low]) as usize,
+        _ => return Err(DetectConnectError::Incomplete),
+    };
+
+    rest.get(2 + name_length)
+        .copied()
+        .ok_or(DetectConnectError::Incomplete)
+}
+
+/// Parse a CONNECT packet, dispatching to the MQTTv3 or MQTTv5 parser depending on its protocol
+/// level
+///
+/// This allows a server to accept both protocol versions on the same port.
+pub fn detect_and_parse_connect(input: &[u8]) -> Result<EitherConnect<'_>, DetectConnectError<'_>> {
+    match peek_protocol_level(input)? {
+        PROTOCOL_LEVEL_V3 => {
+            match crate::v3::packet::mpacket(input).map_err(DetectConnectError::V3)? {
+                (_, crate::v3::packet::MPacket::Connect(connect)) => Ok(EitherConnect::V3(connect)),
+                _ => Err(DetectConnectError::NotAConnect),
+            }
+        }
+        PROTOCOL_LEVEL_V5 => {
+            match crate::v5::packets::MqttPacket::parse_complete(input)
+                .map_err(DetectConnectError::V5)?
+            {
+                crate::v5::packets::MqttPacket::Connect(connect) => Ok(EitherConnect::V5(connect)),
+                _ => Err(DetectConnectError::NotAConnect),
+            }
+        }
+        level => Err(DetectConnectError::UnsupportedProtocolLevel(level)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_and_parse_connect;
+    use super::DetectConnectError;
+    use super::EitherConnect;
+
+    #[test]
+    fn detects_v3_connect() {
+        let input = &[
+            0b0001_0000,
+            17,
+            0x0,
+            0x4, // String length
+            b'M',
+            b'Q',
+            b'T',
+            b'T',
+            0x4,         // Level
+            0b0000_0010, // Connect flags, clean session
+            0x0,
+            0x10, // Keep Alive in secs
+            0x0,  // Client Identifier
+            0x5,
+            b'H',
+            b'E',
+            b'L',
+            b'L',
+            b'O',
+        ];
+
+        let EitherConnect::V3(connect) = detect_and_parse_connect(input).unwrap() else {
+            panic!("Expected a MQTTv3 CONNECT");
+        };
+        assert_eq!(connect.client_id.value, "HELLO");
+        assert_eq!(connect.keep_alive, 16);
+    }
+
+    #[test]
+    fn detects_v5_connect() {
+        let connect = crate::v5::packets::connect::MConnect {
+            client_identifier: "HELLO",
+            username: None,
+            password: None,
+            clean_start: true,
+            will: None,
+            properties: crate::v5::packets::connect::ConnectProperties::new(),
+            keep_alive: 16,
+        };
+        let mut writer = crate::v5::test::TestWriter { buffer: Vec::new() };
+        crate::v5::packets::MqttPacket::Connect(connect.clone())
+            .write(&mut writer)
+            .unwrap();
+
+        assert_eq!(
+            detect_and_parse_connect(&writer.buffer).unwrap(),
+            EitherConnect::V5(connect)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_protocol_level() {
+        let input = &[
+            0b0001_0000,
+            10,
+            0x0,
+            0x4,
+            b'M',
+            b'Q',
+            b'T',
+            b'T',
+            0x3,
+            0x0,
+            0x0,
+            0x0,
+        ];
+
+        assert!(matches!(
+            detect_and_parse_connect(input),
+            Err(DetectConnectError::UnsupportedProtocolLevel(3))
+        ));
+    }
+}
@@ -8,6 +8,9 @@
 #![cfg_attr(test, allow(clippy::disallowed_methods))]
 #![deny(clippy::disallowed_types)]
 
+#[cfg(all(feature = "mqttv3", feature = "mqttv5"))]
+pub mod detect;
+
 #[cfg(feature = "mqttv3")]
 pub mod v3;
 
@@ -26,6 +26,8 @@ pub enum MPacketHeaderError {
     InvalidSubscriptionAck(u8),
     #[error("The will flag and QoS are inconsistent")]
     InconsistentWillFlag,
+    #[error("The will QoS must not be 3")]
+    InvalidWillQos,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -570,6 +570,14 @@ fn mpacketdata(fixed_header: MPacketHeader, input: &[u8]) -> IResult<&[u8], MPac
                 )));
             }
 
+            if will_qos == 3 {
+                return Err(nom::Err::Error(nom::error::Error::from_external_error(
+                    input,
+                    nom::error::ErrorKind::MapRes,
+                    MPacketHeaderError::InvalidWillQos,
+                )));
+            }
+
             let (input, keep_alive) = be_u16(input)?;
 
             // Payload
@@ -832,6 +840,43 @@ fn check_will_consistency() {
         mpacket(input).unwrap_err();
     }
 
+    #[test]
+    fn check_invalid_will_qos() {
+        let input = &[
+            0b0001_0000,
+            23,
+            0x0,
+            0x4, // String length
+            b'M',
+            b'Q',
+            b'T',
+            b'T',
+            0x4,         // Level
+            0b0001_1100, // Connect flags, with Will QoS = 3 and will flag = 1
+            0x0,
+            0x10, // Keel Alive in secs
+            0x0,  // Client Identifier
+            0x5,
+            b'H',
+            b'E',
+            b'L',
+            b'L',
+            b'O',
+            0x0, // Will Topic
+            0x1,
+            b'W',
+            0x0, // Will Payload
+            0x1,
+            0xFF,
+        ];
+
+        let nom::Err::Error(error) = mpacket(input).unwrap_err() else {
+            panic!("Expected a recoverable parse error");
+        };
+        // Rejected right after the connect flags, before the will itself is parsed
+        assert_eq!(error.input, &input[10..]);
+    }
+
     #[tokio::test]
     async fn check_connect_roundtrip() {
         let input = &[
@@ -0,0 +1,189 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+//! Whole packets to and from byte buffers, for transports that do their own framing
+
+use winnow::error::ContextError;
+use winnow::error::ErrMode;
+
+use super::packets::MqttPacket;
+use super::write::MqttWriteError;
+use super::write::WResult;
+use super::write::WriteMqttPacket;
+
+struct VecWriter(Vec<u8>);
+
+impl WriteMqttPacket for VecWriter {
+    type Error = MqttWriteError;
+
+    fn write_byte(&mut self, u: u8) -> WResult<Self> {
+        self.0.push(u);
+        Ok(())
+    }
+
+    fn write_slice(&mut self, u: &[u8]) -> WResult<Self> {
+        self.0.extend_from_slice(u);
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Encode a single packet, including its fixed header
+pub fn encode_packet(packet: &MqttPacket<'_>) -> Result<Vec<u8>, MqttWriteError> {
+    let mut writer = VecWriter(Vec::with_capacity(packet.binary_size() as usize));
+    packet.write(&mut writer)?;
+    Ok(writer.0)
+}
+
+/// Decode a buffer holding exactly one packet, as produced by [`encode_packet`]
+pub fn decode_packet(input: &[u8]) -> Result<MqttPacket<'_>, ErrMode<ContextError>> {
+    MqttPacket::parse_complete(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU16;
+
+    use super::decode_packet;
+    use super::encode_packet;
+    use crate::v5::packets::auth::AuthProperties;
+    use crate::v5::packets::auth::AuthReasonCode;
+    use crate::v5::packets::auth::MAuth;
+    use crate::v5::packets::connack::ConnackProperties;
+    use crate::v5::packets::connack::ConnackReasonCode;
+    use crate::v5::packets::connack::MConnack;
+    use crate::v5::packets::connect::ConnectProperties;
+    use crate::v5::packets::connect::MConnect;
+    use crate::v5::packets::disconnect::DisconnectProperties;
+    use crate::v5::packets::disconnect::DisconnectReasonCode;
+    use crate::v5::packets::disconnect::MDisconnect;
+    use crate::v5::packets::pingreq::MPingreq;
+    use crate::v5::packets::pingresp::MPingresp;
+    use crate::v5::packets::puback::MPuback;
+    use crate::v5::packets::puback::PubackProperties;
+    use crate::v5::packets::puback::PubackReasonCode;
+    use crate::v5::packets::pubcomp::MPubcomp;
+    use crate::v5::packets::pubcomp::PubcompProperties;
+    use crate::v5::packets::pubcomp::PubcompReasonCode;
+    use crate::v5::packets::publish::MPublish;
+    use crate::v5::packets::publish::PublishProperties;
+    use crate::v5::packets::pubrec::MPubrec;
+    use crate::v5::packets::pubrec::PubrecProperties;
+    use crate::v5::packets::pubrec::PubrecReasonCode;
+    use crate::v5::packets::pubrel::MPubrel;
+    use crate::v5::packets::pubrel::PubrelProperties;
+    use crate::v5::packets::pubrel::PubrelReasonCode;
+    use crate::v5::packets::suback::MSuback;
+    use crate::v5::packets::suback::SubackProperties;
+    use crate::v5::packets::suback::SubackReasonCode;
+    use crate::v5::packets::subscribe::MSubscribe;
+    use crate::v5::packets::subscribe::SubscribeProperties;
+    use crate::v5::packets::subscribe::Subscriptions;
+    use crate::v5::packets::unsuback::MUnsuback;
+    use crate::v5::packets::unsuback::UnsubackProperties;
+    use crate::v5::packets::unsuback::UnsubackReasonCode;
+    use crate::v5::packets::unsubscribe::MUnsubscribe;
+    use crate::v5::packets::unsubscribe::UnsubscribeProperties;
+    use crate::v5::packets::unsubscribe::Unsubscriptions;
+    use crate::v5::packets::MqttPacket;
+    use crate::v5::qos::QualityOfService;
+    use crate::v5::variable_header::PacketIdentifier;
+
+    #[test]
+    fn roundtrip_all_packet_kinds() {
+        let packet_identifier = PacketIdentifier(NonZeroU16::new(42).unwrap());
+        // A topic filter "foo/#" with QoS 1
+        let subscriptions = [0, 5, b'f', b'o', b'o', b'/', b'#', 0b0000_0001];
+        let unsubscriptions = [0, 5, b'f', b'o', b'o', b'/', b'#'];
+
+        let packets = [
+            MqttPacket::Auth(MAuth {
+                reason: AuthReasonCode::ContinueAuthentication,
+                properties: AuthProperties::new(),
+            }),
+            MqttPacket::Connack(MConnack {
+                session_present: true,
+                reason_code: ConnackReasonCode::Success,
+                properties: ConnackProperties::new(),
+            }),
+            MqttPacket::Connect(MConnect {
+                client_identifier: "client",
+                username: Some("user"),
+                password: Some(b"secret"),
+                clean_start: true,
+                will: None,
+                properties: ConnectProperties::new(),
+                keep_alive: 30,
+            }),
+            MqttPacket::Disconnect(MDisconnect {
+                reason_code: DisconnectReasonCode::NormalDisconnection,
+                properties: DisconnectProperties::new(),
+            }),
+            MqttPacket::Pingreq(MPingreq),
+            MqttPacket::Pingresp(MPingresp),
+            MqttPacket::Puback(MPuback {
+                packet_identifier,
+                reason: PubackReasonCode::Success,
+                properties: PubackProperties::new(),
+            }),
+            MqttPacket::Pubcomp(MPubcomp {
+                packet_identifier,
+                reason: PubcompReasonCode::Success,
+                properties: PubcompProperties::new(),
+            }),
+            MqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: QualityOfService::AtLeastOnce,
+                retain: true,
+                topic_name: "foo/bar",
+                packet_identifier: Some(packet_identifier),
+                properties: PublishProperties::new(),
+                payload: b"hello",
+            }),
+            MqttPacket::Pubrec(MPubrec {
+                packet_identifier,
+                reason: PubrecReasonCode::Success,
+                properties: PubrecProperties::new(),
+            }),
+            MqttPacket::Pubrel(MPubrel {
+                packet_identifier,
+                reason: PubrelReasonCode::Success,
+                properties: PubrelProperties::new(),
+            }),
+            MqttPacket::Suback(MSuback {
+                packet_identifier,
+                properties: SubackProperties::new(),
+                reasons: &[SubackReasonCode::GrantedQoS1],
+            }),
+            MqttPacket::Subscribe(MSubscribe {
+                packet_identifier,
+                properties: SubscribeProperties::new(),
+                subscriptions: Subscriptions::parse(&mut winnow::Bytes::new(&subscriptions))
+                    .unwrap(),
+            }),
+            MqttPacket::Unsuback(MUnsuback {
+                packet_identifier,
+                properties: UnsubackProperties::new(),
+                reasons: &[UnsubackReasonCode::Success],
+            }),
+            MqttPacket::Unsubscribe(MUnsubscribe {
+                packet_identifier,
+                properties: UnsubscribeProperties::new(),
+                unsubscriptions: Unsubscriptions::parse(&mut winnow::Bytes::new(&unsubscriptions))
+                    .unwrap(),
+            }),
+        ];
+
+        for packet in packets {
+            let encoded = encode_packet(&packet).unwrap();
+            assert_eq!(encoded.len(), packet.binary_size() as usize);
+            assert_eq!(decode_packet(&encoded).unwrap(), packet);
+        }
+    }
+}
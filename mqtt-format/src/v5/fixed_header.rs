@@ -7,7 +7,6 @@
 
 use winnow::binary::bits::bits;
 use winnow::error::ErrMode;
-use winnow::error::FromExternalError;
 use winnow::error::InputError;
 use winnow::error::ParserError;
 use winnow::Bytes;
@@ -67,9 +66,7 @@ pub fn parse(input: &mut &Bytes) -> MResult<MFixedHeader> {
             (2, 0) => PacketType::Connack,
             (3, flags) => PacketType::Publish {
                 dup: (0b1000 & flags) != 0,
-                qos: crate::v5::qos::QualityOfService::try_from((flags & 0b0110) >> 1).map_err(
-                    |e| ErrMode::from_external_error(input, winnow::error::ErrorKind::Verify, e),
-                )?,
+                qos: crate::v5::qos::qos_from_bits(input, (flags & 0b0110) >> 1, "publish QoS")?,
                 retain: (0b0001 & flags) != 0,
             },
             (4, 0) => PacketType::Puback,
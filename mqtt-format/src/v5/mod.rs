@@ -37,9 +37,13 @@
 
 pub mod boolean;
 pub mod bytes;
+#[cfg(feature = "std")]
+mod encoding;
 pub mod fixed_header;
 pub mod integers;
 pub mod packets;
+#[cfg(feature = "std")]
+pub mod pretty;
 pub mod properties;
 pub mod qos;
 pub mod reason_code;
@@ -51,4 +55,9 @@
 #[cfg(test)]
 pub mod test;
 
+#[cfg(feature = "std")]
+pub use self::encoding::decode_packet;
+#[cfg(feature = "std")]
+pub use self::encoding::encode_packet;
+
 pub type MResult<O> = winnow::PResult<O>;
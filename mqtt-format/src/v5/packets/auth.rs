@@ -60,6 +60,19 @@ pub fn parse(input: &mut &'i Bytes) -> MResult<Self> {
         .parse_next(input)
     }
 
+    /// The authentication method, which every AUTH packet has to carry
+    pub fn authentication_method(&self) -> Option<&'i str> {
+        self.properties.authentication_method().map(|am| am.0)
+    }
+
+    pub fn authentication_data(&self) -> Option<&'i [u8]> {
+        self.properties.authentication_data().map(|ad| ad.0)
+    }
+
+    pub fn reason_string(&self) -> Option<&'i str> {
+        self.properties.reason_string().map(|rs| rs.0)
+    }
+
     pub fn binary_size(&self) -> u32 {
         self.reason.binary_size() + self.properties.binary_size()
     }
@@ -93,6 +106,28 @@ fn test_roundtrip_mauth_no_props() {
         });
     }
 
+    #[test]
+    fn continue_authentication_exposes_method_and_data() {
+        let mut writer = crate::v5::test::TestWriter { buffer: Vec::new() };
+        MAuth {
+            reason: AuthReasonCode::ContinueAuthentication,
+            properties: AuthProperties {
+                authentication_method: Some(AuthenticationMethod("SCRAM-SHA-256")),
+                authentication_data: Some(AuthenticationData(b"challenge")),
+                reason_string: None,
+                user_properties: None,
+            },
+        }
+        .write(&mut writer)
+        .unwrap();
+
+        let auth = MAuth::parse(&mut winnow::Bytes::new(&writer.buffer)).unwrap();
+        assert_eq!(auth.reason, AuthReasonCode::ContinueAuthentication);
+        assert_eq!(auth.authentication_method(), Some("SCRAM-SHA-256"));
+        assert_eq!(auth.authentication_data(), Some(&b"challenge"[..]));
+        assert_eq!(auth.reason_string(), None);
+    }
+
     #[test]
     fn test_roundtrip_mauth_props() {
         crate::v5::test::make_roundtrip_test!(MAuth {
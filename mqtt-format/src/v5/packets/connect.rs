@@ -4,9 +4,12 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+use winnow::error::AddContext;
 use winnow::error::ErrMode;
 use winnow::error::InputError;
 use winnow::error::ParserError;
+use winnow::error::StrContext;
+use winnow::stream::Stream;
 use winnow::Bytes;
 use winnow::Parser;
 
@@ -106,8 +109,7 @@ pub fn parse(input: &mut &'i Bytes) -> MResult<Self> {
                 winnow::binary::bits::bool,
                 winnow::binary::bits::bool,
                 winnow::binary::bits::bool,
-                winnow::binary::bits::take(2usize)
-                    .try_map(<QualityOfService as TryFrom<u8>>::try_from),
+                winnow::binary::bits::take(2usize),
                 winnow::binary::bits::bool,
                 winnow::binary::bits::bool,
                 winnow::binary::bits::pattern(0x0, 1usize),
@@ -116,6 +118,17 @@ pub fn parse(input: &mut &'i Bytes) -> MResult<Self> {
             .map_err(|_: ErrMode<InputError<_>>| {
                 ErrMode::from_error_kind(input, winnow::error::ErrorKind::Slice)
             })?;
+            // MQTT-3.1.2-11, MQTT-3.1.2-13 and MQTT-3.1.2-15
+            if !will_flag && (will_retain || will_qos != 0) {
+                return Err(
+                    ErrMode::from_error_kind(input, winnow::error::ErrorKind::Verify).add_context(
+                        input,
+                        &input.checkpoint(),
+                        StrContext::Label("will QoS or will retain without a will"),
+                    ),
+                );
+            }
+            let will_qos = crate::v5::qos::qos_from_bits(input, will_qos, "will QoS")?;
 
             let keep_alive = parse_u16(input)?;
 
@@ -314,6 +327,29 @@ mod test {
     use crate::v5::variable_header::UserProperties;
     use crate::v5::variable_header::WillDelayInterval;
 
+    /// A CONNECT without properties for the client "a" with the given connect flags
+    fn connect_with_flags(flags: u8) -> [u8; 14] {
+        [
+            0x00, 0x04, b'M', b'Q', b'T', b'T', 0x05, flags, 0x00, 0x3C, 0x00, 0x00, 0x01, b'a',
+        ]
+    }
+
+    #[test]
+    fn will_flags_without_will_are_rejected() {
+        // Clean start is set in all of them
+        let accepted = connect_with_flags(0b0000_0010);
+        let will_retain = connect_with_flags(0b0010_0010);
+        let will_qos_1 = connect_with_flags(0b0000_1010);
+        let will_qos_2 = connect_with_flags(0b0001_0010);
+
+        let connect = MConnect::parse(&mut winnow::Bytes::new(&accepted)).unwrap();
+        assert_eq!(connect.will, None);
+
+        for input in [will_retain, will_qos_1, will_qos_2] {
+            assert!(MConnect::parse(&mut winnow::Bytes::new(&input)).is_err());
+        }
+    }
+
     #[test]
     fn test_roundtrip_connect_empty() {
         crate::v5::test::make_roundtrip_test!(MConnect {
@@ -107,6 +107,26 @@ pub fn parse_complete(input: &'i [u8]) -> Result<Self, ErrMode<ContextError>> {
         Self::parse(&mut Bytes::new(input))
     }
 
+    /// Parse the first packet in `input` and return it together with the bytes following it
+    ///
+    /// Calling this in a loop splits a buffer holding several packets into the single packets.
+    pub fn parse_with_remainder(
+        input: &'i [u8],
+    ) -> Result<(Self, &'i [u8]), ErrMode<ContextError>> {
+        let mut input = Bytes::new(input);
+        let packet = Self::parse(&mut input)?;
+
+        Ok((packet, input))
+    }
+
+    /// A multi-line, human readable description of the packet, including its decoded properties
+    ///
+    /// Meant for debugging, the exact output is not stable.
+    #[cfg(feature = "std")]
+    pub fn display_pretty(&self) -> crate::v5::pretty::PrettyMqttPacket<'_, 'i> {
+        crate::v5::pretty::PrettyMqttPacket { packet: self }
+    }
+
     pub fn binary_size(&self) -> u32 {
         let header = MFixedHeader::binary_size();
 
@@ -263,6 +283,40 @@ pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
     }
 }
 
+impl<'i> MqttPacket<'i> {
+    /// Compute the binary size once and keep it next to the packet
+    pub fn into_sized(self) -> SizedMqttPacket<'i> {
+        SizedMqttPacket {
+            encoded_size: self.binary_size(),
+            packet: self,
+        }
+    }
+}
+
+/// A packet together with its precomputed binary size
+///
+/// Computing [`MqttPacket::binary_size`] walks all properties of the packet, which adds up for
+/// large packets whose size is needed several times before they are written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizedMqttPacket<'i> {
+    packet: MqttPacket<'i>,
+    encoded_size: u32,
+}
+
+impl<'i> SizedMqttPacket<'i> {
+    pub fn encoded_size(&self) -> u32 {
+        self.encoded_size
+    }
+
+    pub fn packet(&self) -> &MqttPacket<'i> {
+        &self.packet
+    }
+
+    pub fn into_inner(self) -> MqttPacket<'i> {
+        self.packet
+    }
+}
+
 macro_rules! impl_try_from_packet {
     ($($kind:ty => $name:ident),*) => {
         $(
@@ -333,3 +387,109 @@ pub fn get_kind(&self) -> MqttPacketKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MqttPacket;
+    use crate::v5::packets::pingreq::MPingreq;
+    use crate::v5::packets::pingresp::MPingresp;
+    use crate::v5::packets::publish::MPublish;
+    use crate::v5::packets::publish::PublishProperties;
+    use crate::v5::qos::QualityOfService;
+    use crate::v5::variable_header::ContentType;
+    use crate::v5::variable_header::CorrelationData;
+    use crate::v5::variable_header::MessageExpiryInterval;
+    use crate::v5::variable_header::ResponseTopic;
+    use crate::v5::variable_header::UserProperties;
+
+    #[test]
+    fn sized_packet_matches_binary_size() {
+        let user_properties = [
+            0x26, 0, 3, b'k', b'e', b'y', 0, 5, b'v', b'a', b'l', b'u', b'e',
+        ];
+
+        let packet = MqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtMostOnce,
+            retain: false,
+            topic_name: "foo/bar",
+            packet_identifier: None,
+            properties: PublishProperties {
+                payload_format_indicator: None,
+                message_expiry_interval: Some(MessageExpiryInterval(120)),
+                topic_alias: None,
+                response_topic: Some(ResponseTopic("foo/response")),
+                correlation_data: Some(CorrelationData(&[1, 2, 3, 4])),
+                user_properties: Some(UserProperties(&user_properties[1..])),
+                subscription_identifier: None,
+                content_type: Some(ContentType("application/json")),
+            },
+            payload: &[0xAB; 512],
+        });
+
+        let sized = packet.clone().into_sized();
+        assert_eq!(sized.encoded_size(), packet.binary_size());
+
+        let mut writer = crate::v5::test::TestWriter { buffer: Vec::new() };
+        sized.packet().write(&mut writer).unwrap();
+        assert_eq!(writer.buffer.len(), sized.encoded_size() as usize);
+    }
+
+    #[test]
+    fn parse_with_remainder_returns_following_packets() {
+        let mut writer = crate::v5::test::TestWriter { buffer: Vec::new() };
+        MqttPacket::Pingreq(MPingreq).write(&mut writer).unwrap();
+        let first_len = writer.buffer.len();
+        MqttPacket::Pingresp(MPingresp).write(&mut writer).unwrap();
+
+        let (first, remainder) = MqttPacket::parse_with_remainder(&writer.buffer).unwrap();
+        assert_eq!(first, MqttPacket::Pingreq(MPingreq));
+        assert_eq!(remainder, &writer.buffer[first_len..]);
+
+        let (second, remainder) = MqttPacket::parse_with_remainder(remainder).unwrap();
+        assert_eq!(second, MqttPacket::Pingresp(MPingresp));
+        assert!(remainder.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display_pretty_shows_user_properties() {
+        // The first property identifier is consumed while parsing, so it is not part of the slice
+        let user_properties = [
+            0, 3, b'k', b'e', b'y', 0, 5, b'v', b'a', b'l', b'u', b'e', 0x26, 0, 6, b's', b'o',
+            b'u', b'r', b'c', b'e', 0, 4, b't', b'e', b's', b't',
+        ];
+
+        let packet = MqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtLeastOnce,
+            retain: true,
+            topic_name: "foo/bar",
+            packet_identifier: Some(crate::v5::variable_header::PacketIdentifier(
+                core::num::NonZeroU16::new(7).unwrap(),
+            )),
+            properties: PublishProperties {
+                message_expiry_interval: Some(MessageExpiryInterval(120)),
+                user_properties: Some(UserProperties(&user_properties)),
+                ..PublishProperties::new()
+            },
+            payload: b"hello",
+        });
+
+        assert_eq!(
+            packet.display_pretty().to_string(),
+            "PUBLISH
+  topic: \"foo/bar\"
+  qos: AtLeastOnce
+  packet identifier: 7
+  retain: true
+  duplicate: false
+  payload: 5 bytes
+  properties:
+    message_expiry_interval: 120
+    user property: \"key\" = \"value\"
+    user property: \"source\" = \"test\"
+"
+        );
+    }
+}
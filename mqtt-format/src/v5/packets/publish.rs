@@ -145,6 +145,23 @@ mod test {
     use crate::v5::qos::QualityOfService;
     use crate::v5::variable_header::PacketIdentifier;
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn reserved_qos_in_header_names_the_field() {
+        // PUBLISH with both QoS bits set, followed by an empty topic and no properties
+        let input = [0b0011_0110, 0x03, 0x00, 0x00, 0x00];
+
+        let Err(winnow::error::ErrMode::Backtrack(error)) =
+            crate::v5::packets::MqttPacket::parse_complete(&input)
+        else {
+            panic!("Expected a reserved QoS to fail parsing");
+        };
+        assert!(
+            error.to_string().contains("publish QoS"),
+            "Unexpected error message: {error}"
+        );
+    }
+
     #[test]
     fn test_roundtrip_pubcomp_no_props() {
         let mut writer = crate::v5::test::TestWriter { buffer: Vec::new() };
@@ -6,15 +6,20 @@
 
 use winnow::binary::bits::bits;
 use winnow::combinator::repeat_till;
+use winnow::error::AddContext;
 use winnow::error::ErrMode;
+use winnow::error::FromExternalError;
 use winnow::error::InputError;
 use winnow::error::ParserError;
+use winnow::error::StrContext;
+use winnow::stream::Stream;
 use winnow::Bytes;
 use winnow::Parser;
 
 use crate::v5::properties::define_properties;
 use crate::v5::qos::QualityOfService;
 use crate::v5::strings::parse_string;
+use crate::v5::strings::string_binary_size;
 use crate::v5::strings::write_string;
 use crate::v5::variable_header::PacketIdentifier;
 use crate::v5::variable_header::SubscriptionIdentifier;
@@ -54,20 +59,45 @@ pub struct SubscriptionOptions {
 impl SubscriptionOptions {
     fn parse(input: &mut &Bytes) -> MResult<SubscriptionOptions> {
         winnow::combinator::trace("SubscriptionOptions", |input: &mut &Bytes| {
-            let (_reserved, retain_handling, retain_as_published, no_local, quality_of_service) =
-                bits::<_, _, InputError<(_, usize)>, _, _>((
-                    winnow::binary::bits::pattern(0x0, 2usize),
-                    winnow::binary::bits::take(2usize)
-                        .try_map(<RetainHandling as TryFrom<u8>>::try_from),
-                    winnow::binary::bits::bool,
-                    winnow::binary::bits::bool,
-                    winnow::binary::bits::take(2usize)
-                        .try_map(<QualityOfService as TryFrom<u8>>::try_from),
-                ))
-                .parse_next(input)
-                .map_err(|_: ErrMode<InputError<_>>| {
-                    ErrMode::from_error_kind(input, winnow::error::ErrorKind::Slice)
-                })?;
+            let (reserved, retain_handling, retain_as_published, no_local, quality_of_service): (
+                u8,
+                u8,
+                bool,
+                bool,
+                u8,
+            ) = bits::<_, _, InputError<(_, usize)>, _, _>((
+                winnow::binary::bits::take(2usize),
+                winnow::binary::bits::take(2usize),
+                winnow::binary::bits::bool,
+                winnow::binary::bits::bool,
+                winnow::binary::bits::take(2usize),
+            ))
+            .parse_next(input)
+            .map_err(|_: ErrMode<InputError<_>>| {
+                ErrMode::from_error_kind(input, winnow::error::ErrorKind::Slice)
+            })?;
+
+            // MQTT-3.8.3-5
+            if reserved != 0 {
+                return Err(
+                    ErrMode::from_error_kind(input, winnow::error::ErrorKind::Verify).add_context(
+                        input,
+                        &input.checkpoint(),
+                        StrContext::Label("reserved bits of the subscription options"),
+                    ),
+                );
+            }
+            // MQTT-3.8.3-4
+            let retain_handling = RetainHandling::try_from(retain_handling).map_err(|e| {
+                ErrMode::from_external_error(input, winnow::error::ErrorKind::Verify, e)
+                    .add_context(
+                        input,
+                        &input.checkpoint(),
+                        StrContext::Label("retain handling"),
+                    )
+            })?;
+            let quality_of_service =
+                crate::v5::qos::qos_from_bits(input, quality_of_service, "subscription QoS")?;
 
             Ok(SubscriptionOptions {
                 quality_of_service,
@@ -112,6 +142,11 @@ fn parse(input: &mut &'i Bytes) -> MResult<Subscription<'i>> {
         .parse_next(input)
     }
 
+    pub fn binary_size(&self) -> u32 {
+        // The options are a single byte
+        string_binary_size(self.topic_filter) + 1
+    }
+
     pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
         write_string(buffer, self.topic_filter)?;
         self.options.write(buffer)
@@ -120,7 +155,14 @@ pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
 
 #[derive(Clone)]
 pub struct Subscriptions<'i> {
-    start: &'i [u8],
+    repr: SubscriptionsRepr<'i>,
+}
+
+#[derive(Clone)]
+enum SubscriptionsRepr<'i> {
+    /// Subscriptions in their wire format, already validated while parsing
+    Encoded(&'i [u8]),
+    List(&'i [Subscription<'i>]),
 }
 
 impl<'i> core::cmp::PartialEq for Subscriptions<'i> {
@@ -136,7 +178,8 @@ fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 }
 
 impl<'i> Subscriptions<'i> {
-    fn parse(input: &mut &'i Bytes) -> MResult<Subscriptions<'i>> {
+    /// Parse the encoded subscriptions of a SUBSCRIBE, written with [`Subscription::write`]
+    pub fn parse(input: &mut &'i Bytes) -> MResult<Subscriptions<'i>> {
         winnow::combinator::trace("Subscriptions", |input: &mut &'i Bytes| {
             let start = repeat_till::<_, _, (), _, _, _, _>(
                 1..,
@@ -146,13 +189,31 @@ fn parse(input: &mut &'i Bytes) -> MResult<Subscriptions<'i>> {
             .take()
             .parse_next(input)?;
 
-            Ok(Subscriptions { start })
+            Ok(Subscriptions {
+                repr: SubscriptionsRepr::Encoded(start),
+            })
         })
         .parse_next(input)
     }
 
+    /// Use already constructed subscriptions, each with its own options
+    ///
+    /// Returns `None` if `subscriptions` is empty, as a SUBSCRIBE needs at least one.
+    pub fn from_slice(subscriptions: &'i [Subscription<'i>]) -> Option<Subscriptions<'i>> {
+        if subscriptions.is_empty() {
+            return None;
+        }
+
+        Some(Subscriptions {
+            repr: SubscriptionsRepr::List(subscriptions),
+        })
+    }
+
     pub fn binary_size(&self) -> u32 {
-        self.start.len() as u32
+        match self.repr {
+            SubscriptionsRepr::Encoded(start) => start.len() as u32,
+            SubscriptionsRepr::List(list) => list.iter().map(Subscription::binary_size).sum(),
+        }
     }
 
     pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
@@ -164,29 +225,42 @@ pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
     }
 
     pub fn iter(&self) -> SubscriptionsIter<'i> {
-        SubscriptionsIter {
-            current: Bytes::new(self.start),
-        }
+        let repr = match self.repr {
+            SubscriptionsRepr::Encoded(start) => SubscriptionsIterRepr::Encoded(Bytes::new(start)),
+            SubscriptionsRepr::List(list) => SubscriptionsIterRepr::List(list.iter()),
+        };
+
+        SubscriptionsIter { repr }
     }
 }
 
 #[allow(missing_debug_implementations)]
 pub struct SubscriptionsIter<'i> {
-    current: &'i Bytes,
+    repr: SubscriptionsIterRepr<'i>,
+}
+
+enum SubscriptionsIterRepr<'i> {
+    Encoded(&'i Bytes),
+    List(core::slice::Iter<'i, Subscription<'i>>),
 }
 
 impl<'i> Iterator for SubscriptionsIter<'i> {
     type Item = Subscription<'i>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.current.is_empty() {
-            let sub = Subscription::parse(&mut self.current)
-                .expect("Already parsed subscriptions should be valid");
+        match &mut self.repr {
+            SubscriptionsIterRepr::Encoded(current) => {
+                if current.is_empty() {
+                    return None;
+                }
 
-            return Some(sub);
-        }
+                let sub = Subscription::parse(current)
+                    .expect("Already parsed subscriptions should be valid");
 
-        None
+                Some(sub)
+            }
+            SubscriptionsIterRepr::List(list) => list.next().cloned(),
+        }
     }
 }
 
@@ -236,6 +310,7 @@ mod test {
     use crate::v5::packets::subscribe::Subscription;
     use crate::v5::packets::subscribe::SubscriptionOptions;
     use crate::v5::packets::subscribe::Subscriptions;
+    use crate::v5::packets::subscribe::SubscriptionsRepr;
     use crate::v5::qos::QualityOfService;
     use crate::v5::test::TestWriter;
     use crate::v5::variable_header::PacketIdentifier;
@@ -255,6 +330,26 @@ fn test_roundtrip_subscription() {
         });
     }
 
+    #[test]
+    fn reserved_retain_handling_is_rejected() {
+        // A "foo" topic filter, with retain handling 3 in the options
+        let input = [0x00, 0x03, b'f', b'o', b'o', 0b0011_0000];
+        assert!(Subscription::parse(&mut winnow::Bytes::new(&input)).is_err());
+    }
+
+    #[test]
+    fn reserved_subscription_option_bits_are_rejected() {
+        let input = [0x00, 0x03, b'f', b'o', b'o', 0b0100_0000];
+        assert!(Subscription::parse(&mut winnow::Bytes::new(&input)).is_err());
+
+        let valid = [0x00, 0x03, b'f', b'o', b'o', 0b0010_0000];
+        let subscription = Subscription::parse(&mut winnow::Bytes::new(&valid)).unwrap();
+        assert_eq!(
+            subscription.options.retain_handling,
+            RetainHandling::DoNotSendRetainedMessages
+        );
+    }
+
     #[test]
     fn test_roundtrip_subscribe_no_props() {
         let mut sub_writer = TestWriter { buffer: Vec::new() };
@@ -274,7 +369,7 @@ fn test_roundtrip_subscribe_no_props() {
         crate::v5::test::make_roundtrip_test!(MSubscribe {
             packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
             subscriptions: Subscriptions {
-                start: &sub_writer.buffer
+                repr: SubscriptionsRepr::Encoded(&sub_writer.buffer)
             },
             properties: SubscribeProperties {
                 subscription_identifier: None,
@@ -302,7 +397,7 @@ fn test_roundtrip_subscribe_with_props() {
         crate::v5::test::make_roundtrip_test!(MSubscribe {
             packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
             subscriptions: Subscriptions {
-                start: &sub_writer.buffer
+                repr: SubscriptionsRepr::Encoded(&sub_writer.buffer)
             },
             properties: SubscribeProperties {
                 subscription_identifier: Some(SubscriptionIdentifier(125)),
@@ -310,4 +405,49 @@ fn test_roundtrip_subscribe_with_props() {
             }
         });
     }
+
+    #[test]
+    fn subscriptions_from_slice_keep_their_options() {
+        let options = |quality_of_service| SubscriptionOptions {
+            quality_of_service,
+            no_local: false,
+            retain_as_published: false,
+            retain_handling: RetainHandling::SendRetainedMessagesAlways,
+        };
+        let subscriptions = [
+            Subscription {
+                topic_filter: "a/b",
+                options: options(QualityOfService::AtMostOnce),
+            },
+            Subscription {
+                topic_filter: "a/+",
+                options: options(QualityOfService::AtLeastOnce),
+            },
+            Subscription {
+                topic_filter: "#",
+                options: options(QualityOfService::ExactlyOnce),
+            },
+        ];
+
+        let subscribe = MSubscribe {
+            packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
+            subscriptions: Subscriptions::from_slice(&subscriptions).unwrap(),
+            properties: SubscribeProperties::new(),
+        };
+        let mut writer = TestWriter { buffer: Vec::new() };
+        subscribe.write(&mut writer).unwrap();
+        assert_eq!(writer.buffer.len(), subscribe.binary_size() as usize);
+
+        let parsed = MSubscribe::parse(&mut winnow::Bytes::new(&writer.buffer)).unwrap();
+        assert_eq!(
+            parsed.subscriptions.iter().collect::<Vec<_>>(),
+            subscriptions
+        );
+        assert_eq!(parsed, subscribe);
+    }
+
+    #[test]
+    fn subscriptions_from_empty_slice() {
+        assert!(Subscriptions::from_slice(&[]).is_none());
+    }
 }
@@ -10,6 +10,7 @@
 
 use crate::v5::properties::define_properties;
 use crate::v5::strings::parse_string;
+use crate::v5::strings::string_binary_size;
 use crate::v5::strings::write_string;
 use crate::v5::variable_header::PacketIdentifier;
 use crate::v5::variable_header::SubscriptionIdentifier;
@@ -32,7 +33,14 @@ pub struct UnsubscribeProperties<'i> {
 
 #[derive(Clone)]
 pub struct Unsubscriptions<'i> {
-    start: &'i [u8],
+    repr: UnsubscriptionsRepr<'i>,
+}
+
+#[derive(Clone)]
+enum UnsubscriptionsRepr<'i> {
+    /// Topic filters in their wire format, already validated while parsing
+    Encoded(&'i [u8]),
+    List(&'i [Unsubscription<'i>]),
 }
 
 impl<'i> core::cmp::PartialEq for Unsubscriptions<'i> {
@@ -48,7 +56,8 @@ fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 }
 
 impl<'i> Unsubscriptions<'i> {
-    fn parse(input: &mut &'i Bytes) -> MResult<Unsubscriptions<'i>> {
+    /// Parse the encoded topic filters of an UNSUBSCRIBE, written with [`Unsubscription::write`]
+    pub fn parse(input: &mut &'i Bytes) -> MResult<Unsubscriptions<'i>> {
         winnow::combinator::trace("Unsubscriptions", |input: &mut &'i Bytes| {
             let start = repeat_till::<_, _, (), _, _, _, _>(
                 1..,
@@ -58,13 +67,31 @@ fn parse(input: &mut &'i Bytes) -> MResult<Unsubscriptions<'i>> {
             .take()
             .parse_next(input)?;
 
-            Ok(Unsubscriptions { start })
+            Ok(Unsubscriptions {
+                repr: UnsubscriptionsRepr::Encoded(start),
+            })
         })
         .parse_next(input)
     }
 
+    /// Use already constructed topic filters
+    ///
+    /// Returns `None` if `unsubscriptions` is empty, as an UNSUBSCRIBE needs at least one.
+    pub fn from_slice(unsubscriptions: &'i [Unsubscription<'i>]) -> Option<Unsubscriptions<'i>> {
+        if unsubscriptions.is_empty() {
+            return None;
+        }
+
+        Some(Unsubscriptions {
+            repr: UnsubscriptionsRepr::List(unsubscriptions),
+        })
+    }
+
     pub fn binary_size(&self) -> u32 {
-        self.start.len() as u32
+        match self.repr {
+            UnsubscriptionsRepr::Encoded(start) => start.len() as u32,
+            UnsubscriptionsRepr::List(list) => list.iter().map(Unsubscription::binary_size).sum(),
+        }
     }
 
     pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
@@ -76,29 +103,44 @@ pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
     }
 
     pub fn iter(&self) -> UnsubscriptionsIter<'i> {
-        UnsubscriptionsIter {
-            current: Bytes::new(self.start),
-        }
+        let repr = match self.repr {
+            UnsubscriptionsRepr::Encoded(start) => {
+                UnsubscriptionsIterRepr::Encoded(Bytes::new(start))
+            }
+            UnsubscriptionsRepr::List(list) => UnsubscriptionsIterRepr::List(list.iter()),
+        };
+
+        UnsubscriptionsIter { repr }
     }
 }
 
 #[allow(missing_debug_implementations)]
 pub struct UnsubscriptionsIter<'i> {
-    current: &'i Bytes,
+    repr: UnsubscriptionsIterRepr<'i>,
+}
+
+enum UnsubscriptionsIterRepr<'i> {
+    Encoded(&'i Bytes),
+    List(core::slice::Iter<'i, Unsubscription<'i>>),
 }
 
 impl<'i> Iterator for UnsubscriptionsIter<'i> {
     type Item = Unsubscription<'i>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.current.is_empty() {
-            let sub = Unsubscription::parse(&mut self.current)
-                .expect("Already parsed subscriptions should be valid");
+        match &mut self.repr {
+            UnsubscriptionsIterRepr::Encoded(current) => {
+                if current.is_empty() {
+                    return None;
+                }
 
-            return Some(sub);
-        }
+                let sub = Unsubscription::parse(current)
+                    .expect("Already parsed subscriptions should be valid");
 
-        None
+                Some(sub)
+            }
+            UnsubscriptionsIterRepr::List(list) => list.next().cloned(),
+        }
     }
 }
 
@@ -117,6 +159,10 @@ fn parse(input: &mut &'i Bytes) -> MResult<Self> {
         .parse_next(input)
     }
 
+    pub fn binary_size(&self) -> u32 {
+        string_binary_size(self.topic_filter)
+    }
+
     pub fn write<W: WriteMqttPacket>(&self, buffer: &mut W) -> WResult<W> {
         write_string(buffer, self.topic_filter)
     }
@@ -169,6 +215,7 @@ mod test {
     use crate::v5::packets::unsubscribe::UnsubscribeProperties;
     use crate::v5::packets::unsubscribe::Unsubscription;
     use crate::v5::packets::unsubscribe::Unsubscriptions;
+    use crate::v5::packets::unsubscribe::UnsubscriptionsRepr;
     use crate::v5::test::TestWriter;
     use crate::v5::variable_header::PacketIdentifier;
     use crate::v5::variable_header::SubscriptionIdentifier;
@@ -194,7 +241,7 @@ fn test_roundtrip_unsubscribe_no_props() {
         crate::v5::test::make_roundtrip_test!(MUnsubscribe {
             packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
             unsubscriptions: Unsubscriptions {
-                start: &sub_writer.buffer
+                repr: UnsubscriptionsRepr::Encoded(&sub_writer.buffer)
             },
             properties: UnsubscribeProperties {
                 subscription_identifier: None,
@@ -216,7 +263,7 @@ fn test_roundtrip_unsubscribe_with_props() {
         crate::v5::test::make_roundtrip_test!(MUnsubscribe {
             packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
             unsubscriptions: Unsubscriptions {
-                start: &sub_writer.buffer
+                repr: UnsubscriptionsRepr::Encoded(&sub_writer.buffer)
             },
             properties: UnsubscribeProperties {
                 subscription_identifier: Some(SubscriptionIdentifier(125)),
@@ -224,4 +271,27 @@ fn test_roundtrip_unsubscribe_with_props() {
             }
         });
     }
+
+    #[test]
+    fn unsubscriptions_from_slice_roundtrip() {
+        let unsubscriptions = [
+            Unsubscription {
+                topic_filter: "a/b",
+            },
+            Unsubscription { topic_filter: "#" },
+        ];
+
+        let unsubscribe = MUnsubscribe {
+            packet_identifier: PacketIdentifier(core::num::NonZeroU16::new(88).unwrap()),
+            unsubscriptions: Unsubscriptions::from_slice(&unsubscriptions).unwrap(),
+            properties: UnsubscribeProperties::new(),
+        };
+        let mut writer = TestWriter { buffer: Vec::new() };
+        unsubscribe.write(&mut writer).unwrap();
+        assert_eq!(writer.buffer.len(), unsubscribe.binary_size() as usize);
+
+        let parsed = MUnsubscribe::parse(&mut winnow::Bytes::new(&writer.buffer)).unwrap();
+        assert_eq!(parsed, unsubscribe);
+        assert!(Unsubscriptions::from_slice(&[]).is_none());
+    }
 }
@@ -0,0 +1,158 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+//! Human readable rendering of packets, meant for debugging
+
+use core::fmt;
+
+use super::packets::connect::Will;
+use super::packets::MqttPacket;
+
+/// Renders a single property of a properties struct as one or more lines
+pub(crate) trait PrettyProperty {
+    fn fmt_pretty(&self, name: &str, indent: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// A multi-line description of a [`MqttPacket`], see [`MqttPacket::display_pretty`]
+#[derive(Debug)]
+pub struct PrettyMqttPacket<'a, 'i> {
+    pub(crate) packet: &'a MqttPacket<'i>,
+}
+
+impl<'a, 'i> fmt::Display for PrettyMqttPacket<'a, 'i> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.packet {
+            MqttPacket::Auth(auth) => {
+                writeln!(f, "AUTH")?;
+                writeln!(f, "  reason: {:?}", auth.reason)?;
+                auth.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Connack(connack) => {
+                writeln!(f, "CONNACK")?;
+                writeln!(f, "  reason: {:?}", connack.reason_code)?;
+                writeln!(f, "  session present: {}", connack.session_present)?;
+                connack.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Connect(connect) => {
+                writeln!(f, "CONNECT")?;
+                writeln!(f, "  client identifier: {:?}", connect.client_identifier)?;
+                writeln!(f, "  clean start: {}", connect.clean_start)?;
+                writeln!(f, "  keep alive: {}s", connect.keep_alive)?;
+                if let Some(username) = connect.username {
+                    writeln!(f, "  username: {username:?}")?;
+                }
+                if let Some(password) = connect.password {
+                    // Never print the password itself
+                    writeln!(f, "  password: {} bytes", password.len())?;
+                }
+                if let Some(will) = &connect.will {
+                    fmt_will(f, will)?;
+                }
+                connect.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Disconnect(disconnect) => {
+                writeln!(f, "DISCONNECT")?;
+                writeln!(f, "  reason: {:?}", disconnect.reason_code)?;
+                disconnect.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Pingreq(_) => writeln!(f, "PINGREQ"),
+            MqttPacket::Pingresp(_) => writeln!(f, "PINGRESP"),
+            MqttPacket::Puback(puback) => {
+                writeln!(f, "PUBACK")?;
+                writeln!(f, "  packet identifier: {}", puback.packet_identifier.0)?;
+                writeln!(f, "  reason: {:?}", puback.reason)?;
+                puback.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Pubcomp(pubcomp) => {
+                writeln!(f, "PUBCOMP")?;
+                writeln!(f, "  packet identifier: {}", pubcomp.packet_identifier.0)?;
+                writeln!(f, "  reason: {:?}", pubcomp.reason)?;
+                pubcomp.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Publish(publish) => {
+                writeln!(f, "PUBLISH")?;
+                writeln!(f, "  topic: {:?}", publish.topic_name)?;
+                writeln!(f, "  qos: {:?}", publish.quality_of_service)?;
+                if let Some(packet_identifier) = publish.packet_identifier {
+                    writeln!(f, "  packet identifier: {}", packet_identifier.0)?;
+                }
+                writeln!(f, "  retain: {}", publish.retain)?;
+                writeln!(f, "  duplicate: {}", publish.duplicate)?;
+                writeln!(f, "  payload: {} bytes", publish.payload.len())?;
+                publish.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Pubrec(pubrec) => {
+                writeln!(f, "PUBREC")?;
+                writeln!(f, "  packet identifier: {}", pubrec.packet_identifier.0)?;
+                writeln!(f, "  reason: {:?}", pubrec.reason)?;
+                pubrec.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Pubrel(pubrel) => {
+                writeln!(f, "PUBREL")?;
+                writeln!(f, "  packet identifier: {}", pubrel.packet_identifier.0)?;
+                writeln!(f, "  reason: {:?}", pubrel.reason)?;
+                pubrel.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Suback(suback) => {
+                writeln!(f, "SUBACK")?;
+                writeln!(f, "  packet identifier: {}", suback.packet_identifier.0)?;
+                writeln!(f, "  reasons:")?;
+                for reason in suback.reasons {
+                    writeln!(f, "    {reason:?}")?;
+                }
+                suback.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Subscribe(subscribe) => {
+                writeln!(f, "SUBSCRIBE")?;
+                writeln!(f, "  packet identifier: {}", subscribe.packet_identifier.0)?;
+                writeln!(f, "  subscriptions:")?;
+                for subscription in subscribe.subscriptions.iter() {
+                    let options = &subscription.options;
+                    writeln!(
+                        f,
+                        "    {:?}: qos {:?}, no local {}, retain as published {}, {:?}",
+                        subscription.topic_filter,
+                        options.quality_of_service,
+                        options.no_local,
+                        options.retain_as_published,
+                        options.retain_handling,
+                    )?;
+                }
+                subscribe.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Unsuback(unsuback) => {
+                writeln!(f, "UNSUBACK")?;
+                writeln!(f, "  packet identifier: {}", unsuback.packet_identifier.0)?;
+                writeln!(f, "  reasons:")?;
+                for reason in unsuback.reasons {
+                    writeln!(f, "    {reason:?}")?;
+                }
+                unsuback.properties.fmt_pretty("  ", f)
+            }
+            MqttPacket::Unsubscribe(unsubscribe) => {
+                writeln!(f, "UNSUBSCRIBE")?;
+                writeln!(
+                    f,
+                    "  packet identifier: {}",
+                    unsubscribe.packet_identifier.0
+                )?;
+                writeln!(f, "  topic filters:")?;
+                for unsubscription in unsubscribe.unsubscriptions.iter() {
+                    writeln!(f, "    {:?}", unsubscription.topic_filter)?;
+                }
+                unsubscribe.properties.fmt_pretty("  ", f)
+            }
+        }
+    }
+}
+
+fn fmt_will(f: &mut fmt::Formatter<'_>, will: &Will<'_>) -> fmt::Result {
+    writeln!(f, "  will:")?;
+    writeln!(f, "    topic: {:?}", will.topic)?;
+    writeln!(f, "    qos: {:?}", will.will_qos)?;
+    writeln!(f, "    retain: {}", will.will_retain)?;
+    writeln!(f, "    payload: {} bytes", will.payload.len())?;
+    will.properties.fmt_pretty("    ", f)
+}
@@ -137,6 +137,18 @@ pub fn binary_size(&self) -> u32 {
                 $crate::v5::integers::variable_u32_binary_size(prop_size) + prop_size
             }
 
+            /// Write a `properties:` line followed by one indented line per present property
+            #[cfg(feature = "std")]
+            pub(crate) fn fmt_pretty(&self, indent: &str, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                writeln!(f, "{indent}properties:")?;
+                $(
+                    if let Some(prop) = self.$prop_name.as_ref() {
+                        $crate::v5::pretty::PrettyProperty::fmt_pretty(prop, stringify!($prop_name), indent, f)?;
+                    }
+                )*
+                Ok(())
+            }
+
             pub fn write<W: crate::v5::write::WriteMqttPacket>(&self, buffer: &mut W) -> crate::v5::write::WResult<W> {
                 use crate::v5::variable_header::MqttProperties;
 
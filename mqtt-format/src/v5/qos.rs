@@ -4,7 +4,11 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+use winnow::error::AddContext;
+use winnow::error::ErrMode;
 use winnow::error::FromExternalError;
+use winnow::error::StrContext;
+use winnow::stream::Stream;
 use winnow::Bytes;
 
 use super::write::WResult;
@@ -28,6 +32,24 @@ pub fn parse_qos(input: &mut &Bytes) -> MResult<QualityOfService> {
     })
 }
 
+/// Convert the two QoS bits of a flags field
+///
+/// A reserved value fails with `field` as the label of the error, as a QoS is part of several
+/// packets and the bare value does not say where it came from.
+pub(crate) fn qos_from_bits(
+    input: &&Bytes,
+    bits: u8,
+    field: &'static str,
+) -> MResult<QualityOfService> {
+    QualityOfService::try_from(bits).map_err(|e| {
+        ErrMode::from_external_error(input, winnow::error::ErrorKind::Verify, e).add_context(
+            input,
+            &input.checkpoint(),
+            StrContext::Label(field),
+        )
+    })
+}
+
 #[inline]
 pub fn write_qos<W: WriteMqttPacket>(buffer: &mut W, qos: QualityOfService) -> WResult<W> {
     crate::v5::variable_header::write_u8(buffer, qos.into())
@@ -30,6 +30,15 @@ pub fn binary_size(&self) -> u32 {
                 1
             }
 
+            /// The name of the reason code as given in the specification
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$reason_code_name => <$reason_code_type>::NAME,
+                    )*
+                }
+            }
+
             pub fn write<W: $crate::v5::write::WriteMqttPacket>(&self, buffer: &mut W) -> $crate::v5::write::WResult<W> {
                 match self {
                     $(
@@ -40,62 +49,99 @@ pub fn write<W: $crate::v5::write::WriteMqttPacket>(&self, buffer: &mut W) -> $c
                 Ok(())
             }
         }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
     }
 }
 pub(crate) use make_combined_reason_code;
 
 macro_rules! define_reason_code {
-    ($name:ident => $code:literal) => {
+    ($name:ident => $code:literal, $spec_name:literal) => {
         #[derive(Debug, Copy, Clone, PartialEq)]
         pub struct $name;
         impl $name {
             pub const CODE: u8 = $code;
+            pub const NAME: &'static str = $spec_name;
         }
     };
 }
 
-define_reason_code!(GrantedQoS0 => 0x00);
-define_reason_code!(NormalDisconnection => 0x00);
-define_reason_code!(Success => 0x00);
-define_reason_code!(GrantedQoS1 => 0x01);
-define_reason_code!(GrantedQoS2 => 0x02);
-define_reason_code!(DisconnectWithWillMessage => 0x04);
-define_reason_code!(NoMatchingSubscribers => 0x10);
-define_reason_code!(NoSubscriptionExisted => 0x11);
-define_reason_code!(ContinueAuthentication => 0x18);
-define_reason_code!(ReAuthenticate => 0x19);
-define_reason_code!(UnspecifiedError => 0x80);
-define_reason_code!(MalformedPacket => 0x81);
-define_reason_code!(ProtocolError => 0x82);
-define_reason_code!(ImplementationSpecificError => 0x83);
-define_reason_code!(UnsupportedProtocolVersion => 0x84);
-define_reason_code!(ClientIdentifierNotValid => 0x85);
-define_reason_code!(BadUsernameOrPassword => 0x86);
-define_reason_code!(NotAuthorized => 0x87);
-define_reason_code!(ServerUnavailable => 0x88);
-define_reason_code!(ServerBusy => 0x89);
-define_reason_code!(Banned => 0x8A);
-define_reason_code!(ServerShuttingDown => 0x8B);
-define_reason_code!(BadAuthenticationMethod => 0x8C);
-define_reason_code!(KeepAliveTimeout => 0x8D);
-define_reason_code!(SessionTakenOver => 0x8E);
-define_reason_code!(TopicFilterInvalid => 0x8F);
-define_reason_code!(TopicNameInvalid => 0x90);
-define_reason_code!(PacketIdentifierInUse => 0x91);
-define_reason_code!(PacketIdentifierNotFound => 0x92);
-define_reason_code!(ReceiveMaximumExceeded => 0x93);
-define_reason_code!(TopicAliasInvalid => 0x94);
-define_reason_code!(PacketTooLarge => 0x95);
-define_reason_code!(MessageRateTooHigh => 0x96);
-define_reason_code!(QuotaExceeded => 0x97);
-define_reason_code!(AdministrativeAction => 0x98);
-define_reason_code!(PayloadFormatInvalid => 0x99);
-define_reason_code!(RetainNotSupported => 0x9A);
-define_reason_code!(QoSNotSupported => 0x9B);
-define_reason_code!(UseAnotherServer => 0x9C);
-define_reason_code!(ServerMoved => 0x9D);
-define_reason_code!(SharedSubscriptionsNotSupported => 0x9E);
-define_reason_code!(ConnectionRateExceeded => 0x9F);
-define_reason_code!(MaximumConnectTime => 0xA0);
-define_reason_code!(SubscriptionIdentifiersNotSupported => 0xA1);
-define_reason_code!(WildcardSubscriptionsNotSupported => 0xA2);
+define_reason_code!(GrantedQoS0 => 0x00, "Granted QoS 0");
+define_reason_code!(NormalDisconnection => 0x00, "Normal disconnection");
+define_reason_code!(Success => 0x00, "Success");
+define_reason_code!(GrantedQoS1 => 0x01, "Granted QoS 1");
+define_reason_code!(GrantedQoS2 => 0x02, "Granted QoS 2");
+define_reason_code!(DisconnectWithWillMessage => 0x04, "Disconnect with Will Message");
+define_reason_code!(NoMatchingSubscribers => 0x10, "No matching subscribers");
+define_reason_code!(NoSubscriptionExisted => 0x11, "No subscription existed");
+define_reason_code!(ContinueAuthentication => 0x18, "Continue authentication");
+define_reason_code!(ReAuthenticate => 0x19, "Re-authenticate");
+define_reason_code!(UnspecifiedError => 0x80, "Unspecified error");
+define_reason_code!(MalformedPacket => 0x81, "Malformed Packet");
+define_reason_code!(ProtocolError => 0x82, "Protocol Error");
+define_reason_code!(ImplementationSpecificError => 0x83, "Implementation specific error");
+define_reason_code!(UnsupportedProtocolVersion => 0x84, "Unsupported Protocol Version");
+define_reason_code!(ClientIdentifierNotValid => 0x85, "Client Identifier not valid");
+define_reason_code!(BadUsernameOrPassword => 0x86, "Bad User Name or Password");
+define_reason_code!(NotAuthorized => 0x87, "Not authorized");
+define_reason_code!(ServerUnavailable => 0x88, "Server unavailable");
+define_reason_code!(ServerBusy => 0x89, "Server busy");
+define_reason_code!(Banned => 0x8A, "Banned");
+define_reason_code!(ServerShuttingDown => 0x8B, "Server shutting down");
+define_reason_code!(BadAuthenticationMethod => 0x8C, "Bad authentication method");
+define_reason_code!(KeepAliveTimeout => 0x8D, "Keep Alive timeout");
+define_reason_code!(SessionTakenOver => 0x8E, "Session taken over");
+define_reason_code!(TopicFilterInvalid => 0x8F, "Topic Filter invalid");
+define_reason_code!(TopicNameInvalid => 0x90, "Topic Name invalid");
+define_reason_code!(PacketIdentifierInUse => 0x91, "Packet Identifier in use");
+define_reason_code!(PacketIdentifierNotFound => 0x92, "Packet Identifier not found");
+define_reason_code!(ReceiveMaximumExceeded => 0x93, "Receive Maximum exceeded");
+define_reason_code!(TopicAliasInvalid => 0x94, "Topic Alias invalid");
+define_reason_code!(PacketTooLarge => 0x95, "Packet too large");
+define_reason_code!(MessageRateTooHigh => 0x96, "Message rate too high");
+define_reason_code!(QuotaExceeded => 0x97, "Quota exceeded");
+define_reason_code!(AdministrativeAction => 0x98, "Administrative action");
+define_reason_code!(PayloadFormatInvalid => 0x99, "Payload format invalid");
+define_reason_code!(RetainNotSupported => 0x9A, "Retain not supported");
+define_reason_code!(QoSNotSupported => 0x9B, "QoS not supported");
+define_reason_code!(UseAnotherServer => 0x9C, "Use another server");
+define_reason_code!(ServerMoved => 0x9D, "Server moved");
+define_reason_code!(SharedSubscriptionsNotSupported => 0x9E, "Shared Subscriptions not supported");
+define_reason_code!(ConnectionRateExceeded => 0x9F, "Connection rate exceeded");
+define_reason_code!(MaximumConnectTime => 0xA0, "Maximum connect time");
+define_reason_code!(SubscriptionIdentifiersNotSupported => 0xA1, "Subscription Identifiers not supported");
+define_reason_code!(WildcardSubscriptionsNotSupported => 0xA2, "Wildcard Subscriptions not supported");
+
+#[cfg(test)]
+mod tests {
+    use crate::v5::packets::connack::ConnackReasonCode;
+    use crate::v5::packets::disconnect::DisconnectReasonCode;
+    use crate::v5::packets::puback::PubackReasonCode;
+    use crate::v5::packets::suback::SubackReasonCode;
+
+    #[test]
+    fn reason_codes_render_spec_names() {
+        assert_eq!(ConnackReasonCode::Success.to_string(), "Success");
+        assert_eq!(
+            ConnackReasonCode::BadUsernameOrPassword.to_string(),
+            "Bad User Name or Password"
+        );
+        assert_eq!(
+            DisconnectReasonCode::NormalDisconnection.as_str(),
+            "Normal disconnection"
+        );
+        assert_eq!(
+            DisconnectReasonCode::DisconnectWithWillMessage.as_str(),
+            "Disconnect with Will Message"
+        );
+        assert_eq!(
+            PubackReasonCode::NoMatchingSubscribers.to_string(),
+            "No matching subscribers"
+        );
+        assert_eq!(SubackReasonCode::GrantedQoS2.to_string(), "Granted QoS 2");
+    }
+}
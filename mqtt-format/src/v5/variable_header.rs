@@ -92,6 +92,13 @@ fn write<W: $crate::v5::write::WriteMqttPacket>(&self, buffer: &mut W)
                 }
             }
 
+            #[cfg(feature = "std")]
+            impl< $($tylt)? > $crate::v5::pretty::PrettyProperty for $name < $($tylt)? > {
+                fn fmt_pretty(&self, name: &str, indent: &str, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    writeln!(f, "{indent}  {name}: {:?}", self.0)
+                }
+            }
+
             impl<'i> From< $name <$($tylt)?> > for Property<'i> {
                 fn from(value: $name <$($tylt)?>) -> Property<'i> {
                     Property::$name(value)
@@ -348,6 +355,21 @@ fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'i> crate::v5::pretty::PrettyProperty for UserProperties<'i> {
+    fn fmt_pretty(
+        &self,
+        _name: &str,
+        indent: &str,
+        f: &mut core::fmt::Formatter<'_>,
+    ) -> core::fmt::Result {
+        for UserProperty { key, value } in self.iter() {
+            writeln!(f, "{indent}  user property: {key:?} = {value:?}")?;
+        }
+        Ok(())
+    }
+}
+
 impl<'i> MqttProperties<'i> for UserProperties<'i> {
     const IDENTIFIER: u32 = 0x26;
     const ALLOW_REPEATING: bool = true;
@@ -0,0 +1,233 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+use super::MqttClient;
+use crate::bytes::MqttBytes;
+use crate::packets::auth::AuthProperties;
+use crate::string::MqttString;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReauthenticationError {
+    #[error("No handler was configured to continue the authentication exchange")]
+    NoAuthHandler,
+
+    #[error("The connection ended before the re-authentication completed")]
+    Aborted,
+
+    #[error("There is no connection to re-authenticate")]
+    NotConnected,
+
+    #[error("The connection was not authenticated with an Authentication Method")]
+    NotAuthenticated,
+
+    #[error("The re-authentication used {used:?}, but the connection was authenticated with {connected:?}")]
+    MethodMismatch { used: String, connected: String },
+}
+
+impl MqttClient {
+    /// Start a re-authentication with the server
+    ///
+    /// The exchange is driven by the `on_auth_continue` handler, which gets called for every
+    /// AUTH packet with the 'Continue Authentication' reason the server sends.
+    ///
+    /// Only a connection whose CONNECT had an Authentication Method can be re-authenticated, and
+    /// only with that same method (MQTT-4.12.1-1).
+    pub async fn reauthenticate(
+        &self,
+        method: MqttString,
+        data: MqttBytes,
+    ) -> Result<Reauthentication, ReauthenticationError> {
+        let mut inner = self.inner.lock().await;
+        let inner = &mut *inner;
+
+        let Some(conn_state) = &mut inner.connection_state else {
+            tracing::error!("No connection state found");
+            return Err(ReauthenticationError::NotConnected);
+        };
+
+        let Some(connected_method) = conn_state.authentication_method.as_ref() else {
+            return Err(ReauthenticationError::NotAuthenticated);
+        };
+
+        if connected_method.as_str() != method.as_ref() {
+            return Err(ReauthenticationError::MethodMismatch {
+                used: method.as_ref().to_string(),
+                connected: connected_method.clone(),
+            });
+        }
+
+        let mut properties = AuthProperties::new();
+        properties
+            .with_authentication_method(method.as_ref().to_string())
+            .with_authentication_data(data.as_ref().to_vec());
+
+        let packet =
+            mqtt_format::v5::packets::MqttPacket::Auth(mqtt_format::v5::packets::auth::MAuth {
+                reason: mqtt_format::v5::packets::auth::AuthReasonCode::ReAuthenticate,
+                properties: properties.as_ref(),
+            });
+
+        let (on_complete, recv) = futures::channel::oneshot::channel();
+        inner
+            .outstanding_callbacks
+            .set_reauthentication(ReauthenticationCallback {
+                method,
+                on_complete,
+            });
+
+        conn_state
+            .conn_write
+            .send(packet)
+            .await
+            .map_err(|_| ReauthenticationError::Aborted)?;
+
+        Ok(Reauthentication { recv })
+    }
+}
+
+pub(crate) struct ReauthenticationCallback {
+    pub(crate) method: MqttString,
+    pub(crate) on_complete: futures::channel::oneshot::Sender<Result<(), ReauthenticationError>>,
+}
+
+pub struct Reauthentication {
+    recv: futures::channel::oneshot::Receiver<Result<(), ReauthenticationError>>,
+}
+
+impl Reauthentication {
+    pub async fn completed(self) -> Result<(), ReauthenticationError> {
+        self.recv
+            .await
+            .unwrap_or(Err(ReauthenticationError::Aborted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v5::packets::auth::AuthProperties;
+    use mqtt_format::v5::packets::auth::AuthReasonCode;
+    use mqtt_format::v5::packets::auth::MAuth;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::variable_header::AuthenticationData;
+    use mqtt_format::v5::variable_header::AuthenticationMethod;
+
+    use super::ReauthenticationError;
+    use crate::client::MqttClient;
+
+    #[tokio::test]
+    async fn reauthenticate_round_trip() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::builder()
+            .with_on_auth_continue(Box::new(|method, data| {
+                assert_eq!(method, "TEST");
+                assert_eq!(data, Some(&b"challenge"[..]));
+                b"response".to_vec()
+            }))
+            .build()
+            .await
+            .unwrap();
+
+        let mut connector = crate::test::make_connector(transport);
+        connector
+            .properties_mut()
+            .with_authentication_method("TEST".to_string());
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+
+        let reauth = client
+            .reauthenticate(
+                "TEST".try_into().unwrap(),
+                b"initial".to_vec().try_into().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Auth(auth) = packet.get() else {
+            panic!("Expected an AUTH packet, got: {packet:?}");
+        };
+        assert_eq!(auth.reason, AuthReasonCode::ReAuthenticate);
+        assert_eq!(
+            auth.properties.authentication_data(),
+            Some(&AuthenticationData(b"initial"))
+        );
+
+        server
+            .send(FormatMqttPacket::Auth(MAuth {
+                reason: AuthReasonCode::ContinueAuthentication,
+                properties: AuthProperties {
+                    authentication_method: Some(AuthenticationMethod("TEST")),
+                    authentication_data: Some(AuthenticationData(b"challenge")),
+                    reason_string: None,
+                    user_properties: None,
+                },
+            }))
+            .await;
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Auth(auth) = packet.get() else {
+            panic!("Expected an AUTH packet, got: {packet:?}");
+        };
+        assert_eq!(auth.reason, AuthReasonCode::ContinueAuthentication);
+        assert_eq!(
+            auth.properties.authentication_method(),
+            Some(&AuthenticationMethod("TEST"))
+        );
+        assert_eq!(
+            auth.properties.authentication_data(),
+            Some(&AuthenticationData(b"response"))
+        );
+
+        server
+            .send(FormatMqttPacket::Auth(MAuth {
+                reason: AuthReasonCode::Success,
+                properties: AuthProperties::new(),
+            }))
+            .await;
+
+        reauth.completed().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_requires_an_authenticated_connection() {
+        let client = MqttClient::new_with_default_handlers();
+        let _server = crate::test::connect(&client).await;
+
+        let result = client
+            .reauthenticate(
+                "TEST".try_into().unwrap(),
+                b"initial".to_vec().try_into().unwrap(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ReauthenticationError::NotAuthenticated)
+        ));
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_requires_the_method_of_the_connect() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector
+            .properties_mut()
+            .with_authentication_method("TEST".to_string());
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+
+        let result = client
+            .reauthenticate(
+                "OTHER".try_into().unwrap(),
+                b"initial".to_vec().try_into().unwrap(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ReauthenticationError::MethodMismatch { .. })
+        ));
+    }
+}
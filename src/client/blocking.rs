@@ -0,0 +1,261 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+//! A blocking wrapper around [`MqttClient`] for synchronous code
+
+use futures::StreamExt;
+
+use super::connect::MqttClientConnectError;
+use super::connect::MqttClientConnector;
+use super::incoming::IncomingPublishes;
+use super::send::Publish;
+use super::send::PublishFailed;
+use super::subscribe::SubscribeError;
+use super::subscribe::Subscribed;
+use super::MqttClient;
+use crate::qos::QualityOfService;
+use crate::string::MqttString;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockingPublishError {
+    #[error("Could not send the publish")]
+    Send,
+
+    #[error(transparent)]
+    Failed(#[from] PublishFailed),
+}
+
+/// A [`MqttClient`] that owns its own tokio runtime
+///
+/// The runtime drives the connection on a dedicated worker thread, so received packets and keep
+/// alive pings are handled even while the client sits idle in between calls.
+pub struct BlockingMqttClient {
+    runtime: tokio::runtime::Runtime,
+    client: MqttClient,
+}
+
+impl BlockingMqttClient {
+    pub fn new(client: MqttClient) -> std::io::Result<BlockingMqttClient> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("cloudmqtt-blocking")
+            .enable_all()
+            .build()?;
+
+        Ok(BlockingMqttClient { runtime, client })
+    }
+
+    /// The wrapped async client
+    pub fn client(&self) -> &MqttClient {
+        &self.client
+    }
+
+    /// Connect and run the background task of the connection on the owned runtime
+    pub fn connect(&self, connector: MqttClientConnector) -> Result<(), MqttClientConnectError> {
+        let connected = self.runtime.block_on(self.client.connect(connector))?;
+        self.runtime.spawn(connected.run_until_disconnect());
+
+        Ok(())
+    }
+
+    /// Publish and block until the server acknowledged it, as far as its QoS requires
+    pub fn publish(&self, publish: Publish) -> Result<(), BlockingPublishError> {
+        self.runtime.block_on(async {
+            let published = self
+                .client
+                .publish(publish)
+                .await
+                .map_err(|()| BlockingPublishError::Send)?;
+
+            Ok(published.acknowledged().await?)
+        })
+    }
+
+    /// Subscribe and block until the server answered with a SUBACK
+    pub fn subscribe(
+        &self,
+        subscriptions: &[(MqttString, QualityOfService)],
+    ) -> Result<Subscribed, SubscribeError> {
+        self.runtime.block_on(self.client.subscribe(subscriptions))
+    }
+
+    /// Receive the publishes sent by the server, see [`MqttClient::incoming_publishes`]
+    pub fn incoming_publishes(&self, capacity: usize) -> BlockingIncomingPublishes<'_> {
+        let incoming = self
+            .runtime
+            .block_on(self.client.incoming_publishes(capacity));
+
+        BlockingIncomingPublishes {
+            runtime: &self.runtime,
+            incoming,
+        }
+    }
+}
+
+/// An iterator over the publishes received by a [`BlockingMqttClient`]
+///
+/// Each call to `next` blocks until a publish arrives, or returns `None` once the client
+/// replaced or dropped the stream.
+pub struct BlockingIncomingPublishes<'c> {
+    runtime: &'c tokio::runtime::Runtime,
+    incoming: IncomingPublishes,
+}
+
+impl Iterator for BlockingIncomingPublishes<'_> {
+    type Item = crate::packets::publish::Publish;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.incoming.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::puback::MPuback;
+    use mqtt_format::v5::packets::puback::PubackProperties;
+    use mqtt_format::v5::packets::puback::PubackReasonCode;
+    use mqtt_format::v5::packets::publish::MPublish;
+    use mqtt_format::v5::packets::publish::PublishProperties;
+    use mqtt_format::v5::packets::suback::MSuback;
+    use mqtt_format::v5::packets::suback::SubackProperties;
+    use mqtt_format::v5::packets::suback::SubackReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+
+    use super::BlockingMqttClient;
+    use crate::client::connect::CleanStart;
+    use crate::client::connect::ConnectionStatus;
+    use crate::client::connect::MqttClientConnector;
+    use crate::client::send::Publish;
+    use crate::client::MqttClient;
+    use crate::client_identifier::ProposedClientIdentifier;
+    use crate::keep_alive::KeepAlive;
+    use crate::qos::QualityOfService;
+
+    #[test]
+    fn publish_and_subscribe_without_a_runtime() {
+        let (transport, mut server) = crate::test::make_transport();
+
+        // The broker runs on its own thread, the test itself stays synchronous
+        let broker = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                server.accept_connect().await;
+
+                let packet = server.receive().await;
+                let FormatMqttPacket::Subscribe(subscribe) = packet.get() else {
+                    panic!("Expected a SUBSCRIBE, got: {packet:?}");
+                };
+                server
+                    .send(FormatMqttPacket::Suback(MSuback {
+                        packet_identifier: subscribe.packet_identifier,
+                        properties: SubackProperties::new(),
+                        reasons: &[SubackReasonCode::GrantedQoS1],
+                    }))
+                    .await;
+
+                let packet = server.receive().await;
+                let FormatMqttPacket::Publish(publish) = packet.get() else {
+                    panic!("Expected a PUBLISH, got: {packet:?}");
+                };
+                server
+                    .send(FormatMqttPacket::Puback(MPuback {
+                        packet_identifier: publish.packet_identifier.unwrap(),
+                        reason: PubackReasonCode::Success,
+                        properties: PubackProperties::new(),
+                    }))
+                    .await;
+
+                // Echo the publish back to the subscriber
+                server
+                    .send(FormatMqttPacket::Publish(MPublish {
+                        duplicate: false,
+                        quality_of_service: mqtt_format::v5::qos::QualityOfService::AtMostOnce,
+                        retain: false,
+                        topic_name: publish.topic_name,
+                        packet_identifier: None,
+                        properties: PublishProperties::new(),
+                        payload: publish.payload,
+                    }))
+                    .await;
+            });
+        });
+
+        let client = BlockingMqttClient::new(MqttClient::new_with_default_handlers()).unwrap();
+        let mut incoming = client.incoming_publishes(1);
+        client
+            .connect(crate::test::make_connector(transport))
+            .unwrap();
+
+        let subscribed = client
+            .subscribe(&[("foo/#".try_into().unwrap(), QualityOfService::AtLeastOnce)])
+            .unwrap();
+        assert_eq!(subscribed.reason_codes(), [SubackReasonCode::GrantedQoS1]);
+
+        client
+            .publish(Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtLeastOnce,
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .unwrap();
+
+        let received = incoming.next().unwrap();
+        assert_eq!(received.topic(), "foo/bar");
+        assert_eq!(received.payload(), b"hello");
+
+        broker.join().unwrap();
+    }
+
+    #[test]
+    fn keeps_the_connection_alive_while_idle() {
+        let (transport, mut server) = crate::test::make_transport();
+
+        let broker = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                server.accept_connect().await;
+
+                let packet =
+                    tokio::time::timeout(std::time::Duration::from_secs(5), server.receive())
+                        .await
+                        .expect("The idle client did not send a PINGREQ");
+                assert!(
+                    matches!(packet.get(), FormatMqttPacket::Pingreq(_)),
+                    "Expected a PINGREQ, got: {packet:?}"
+                );
+                server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+
+                server
+            })
+        });
+
+        let client = BlockingMqttClient::new(MqttClient::new_with_default_handlers()).unwrap();
+        client
+            .connect(MqttClientConnector::new(
+                transport,
+                ProposedClientIdentifier::new_potetially_accepted("test").unwrap(),
+                CleanStart::Yes,
+                KeepAlive::Seconds(1.try_into().unwrap()),
+            ))
+            .unwrap();
+
+        // Nothing calls into the client while the keep alive passes
+        let _server = broker.join().unwrap();
+
+        assert_eq!(
+            client.runtime.block_on(client.client().connection_state()),
+            ConnectionStatus::Connected
+        );
+    }
+}
@@ -4,14 +4,18 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use futures::lock::Mutex;
 
 use super::send::Callbacks;
 use super::send::ClientHandlers;
+use super::send::OnAuthContinueFn;
+use super::send::OnDroppedMessageFn;
 use super::send::OnPacketRecvFn;
 use super::send::OnQos1AcknowledgeFn;
+use super::send::OnRedirectFn;
 use super::InnerClient;
 use super::MqttClient;
 
@@ -36,6 +40,21 @@ pub fn with_handle_qos1_acknowledge(mut self, f: OnQos1AcknowledgeFn) -> Self {
         self
     }
 
+    pub fn with_on_auth_continue(mut self, f: OnAuthContinueFn) -> Self {
+        self.handlers.on_auth_continue = Some(f);
+        self
+    }
+
+    pub fn with_on_dropped_message(mut self, f: OnDroppedMessageFn) -> Self {
+        self.handlers.on_dropped_message = f;
+        self
+    }
+
+    pub fn with_on_redirect(mut self, f: OnRedirectFn) -> Self {
+        self.handlers.on_redirect = Some(f);
+        self
+    }
+
     pub async fn build(self) -> Result<super::MqttClient, MqttClientBuilderError> {
         Ok({
             MqttClient {
@@ -44,6 +63,9 @@ pub async fn build(self) -> Result<super::MqttClient, MqttClientBuilderError> {
                     session_state: None,
                     default_handlers: self.handlers,
                     outstanding_callbacks: Callbacks::new(),
+                    incoming_publishes: None,
+                    events: None,
+                    connecting: Arc::new(AtomicBool::new(false)),
                 })),
             }
         })
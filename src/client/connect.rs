@@ -13,6 +13,7 @@
 use tokio_util::codec::FramedRead;
 use tokio_util::codec::FramedWrite;
 
+use super::events::ClientEvent;
 use super::MqttClient;
 use crate::bytes::MqttBytes;
 use crate::client::state::OutstandingPackets;
@@ -22,7 +23,11 @@
 use crate::client_identifier::ProposedClientIdentifier;
 use crate::codecs::MqttPacketCodecError;
 use crate::keep_alive::KeepAlive;
+use crate::packet_identifier::PacketIdentifierAllocator;
+use crate::packet_identifier::SequentialPacketIdentifiers;
 use crate::packets::connack::ConnackPropertiesView;
+use crate::packets::disconnect::DisconnectPropertiesView;
+use crate::packets::disconnect::DisconnectReasonCode;
 use crate::string::MqttString;
 use crate::transport::MqttConnectTransport;
 use crate::transport::MqttConnection;
@@ -75,7 +80,8 @@ pub enum MqttClientConnectError {
     #[error("An error occured while encoding or sending an MQTT Packet")]
     Send(#[source] MqttPacketCodecError),
 
-    #[error("An error occured while decoding or receiving an MQTT Packet")]
+    /// The answer to the CONNECT could not be received, e.g. because it was malformed
+    #[error("Could not receive or decode a packet while awaiting CONNACK")]
     Receive(#[source] MqttPacketCodecError),
 
     #[error("The transport unexpectedly closed")]
@@ -83,10 +89,45 @@ pub enum MqttClientConnectError {
 
     #[error("The server sent a response with a protocol error: {reason}")]
     ServerProtocolError { reason: &'static str },
+
+    #[error("The server continued the authentication, but no handler was configured to answer")]
+    NoAuthHandler,
+
+    #[error("The server assigned a client identifier, but the connector requires its own")]
+    ServerAssignedClientIdentifier,
+
+    #[error("The client only speaks MQTTv5, but {:?} was requested", .0)]
+    UnsupportedProtocolLevel(ProtocolLevel),
+
+    #[error("The CONNECT would be {size} bytes, but at most {maximum} bytes are allowed")]
+    ConnectPacketTooLarge { size: u32, maximum: u32 },
+}
+
+/// The MQTT version a connection is made with, sent as the protocol level in the CONNECT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolLevel {
+    /// MQTT 3.1, protocol level 3
+    V3_1,
+    /// MQTT 3.1.1, protocol level 4
+    V3_1_1,
+    /// MQTT 5.0, protocol level 5
+    #[default]
+    V5,
+}
+
+impl ProtocolLevel {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            ProtocolLevel::V3_1 => 3,
+            ProtocolLevel::V3_1_1 => 4,
+            ProtocolLevel::V5 => 5,
+        }
+    }
 }
 
 pub struct MqttClientConnector {
-    transport: MqttConnectTransport,
+    // Only taken out when connecting, which consumes the connector
+    transport: Option<MqttConnectTransport>,
     client_identifier: ProposedClientIdentifier,
     clean_start: CleanStart,
     keep_alive: KeepAlive,
@@ -94,6 +135,12 @@ pub struct MqttClientConnector {
     username: Option<MqttString>,
     password: Option<MqttBytes>,
     will: Option<MqttWill>,
+    write_timeout: Option<Duration>,
+    require_client_provided_id: bool,
+    protocol_level: ProtocolLevel,
+    packet_identifier_allocator: Box<dyn PacketIdentifierAllocator>,
+    read_buffer_capacity: Option<usize>,
+    maximum_connect_packet_size: Option<u32>,
 }
 
 impl MqttClientConnector {
@@ -104,7 +151,7 @@ pub fn new(
         keep_alive: KeepAlive,
     ) -> MqttClientConnector {
         MqttClientConnector {
-            transport,
+            transport: Some(transport),
             client_identifier,
             clean_start,
             keep_alive,
@@ -112,6 +159,12 @@ pub fn new(
             username: None,
             password: None,
             will: None,
+            write_timeout: None,
+            require_client_provided_id: false,
+            protocol_level: ProtocolLevel::default(),
+            packet_identifier_allocator: Box::<SequentialPacketIdentifiers>::default(),
+            read_buffer_capacity: None,
+            maximum_connect_packet_size: None,
         }
     }
 
@@ -130,60 +183,388 @@ pub fn with_will(&mut self, will: MqttWill) -> &mut Self {
         self
     }
 
+    /// Start with a read buffer of `capacity` bytes
+    ///
+    /// The buffer grows as needed, but starting with room for the usual packet size saves
+    /// reallocations when receiving large publishes.
+    pub fn with_read_buffer_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.read_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Refuse to send a CONNECT, including its will, that is larger than `maximum` bytes
+    ///
+    /// Set this to the maximum packet size of the server to get a
+    /// [`MqttClientConnectError::ConnectPacketTooLarge`] instead of the server closing the
+    /// connection.
+    pub fn with_maximum_connect_packet_size(&mut self, maximum: u32) -> &mut Self {
+        self.maximum_connect_packet_size = Some(maximum);
+        self
+    }
+
+    /// Give up on sending a packet after `write_timeout`, which also ends the connection
+    ///
+    /// Without a write timeout a peer that stops reading blocks every send indefinitely.
+    pub fn with_write_timeout(&mut self, write_timeout: Duration) -> &mut Self {
+        self.write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// Use `allocator` for the packet identifiers of this connection instead of counting up from 1
+    pub fn with_packet_identifier_allocator(
+        &mut self,
+        allocator: impl PacketIdentifierAllocator + 'static,
+    ) -> &mut Self {
+        self.packet_identifier_allocator = Box::new(allocator);
+        self
+    }
+
+    /// Fail connecting if the server assigns a client identifier instead of using the proposed one
+    pub fn with_require_client_provided_id(&mut self, require: bool) -> &mut Self {
+        self.require_client_provided_id = require;
+        self
+    }
+
+    /// The protocol level to connect with, MQTTv5 by default
+    ///
+    /// Everything after the CONNECT is only implemented for MQTTv5, so connecting with any other
+    /// level fails with [`MqttClientConnectError::UnsupportedProtocolLevel`] before anything is
+    /// sent.
+    pub fn with_protocol_level(&mut self, protocol_level: ProtocolLevel) -> &mut Self {
+        self.protocol_level = protocol_level;
+        self
+    }
+
+    pub fn protocol_level(&self) -> ProtocolLevel {
+        self.protocol_level
+    }
+
+    /// Ask the server to send a response information in its CONNACK
+    ///
+    /// Servers use it to tell the client the base of topics to use for request/response, it is
+    /// available as [`ConnackPropertiesView::response_information`] once connected.
+    pub fn with_request_response_information(&mut self, request: bool) -> &mut Self {
+        self.properties
+            .with_request_response_information(u8::from(request));
+        self
+    }
+
+    /// Ask the server to keep the session for `seconds` after the connection closed
+    ///
+    /// Without this the session ends with the connection, `u32::MAX` keeps it forever.
+    pub fn with_session_expiry_interval(&mut self, seconds: u32) -> &mut Self {
+        self.properties.with_session_expiry_interval(seconds);
+        self
+    }
+
+    /// Whether the server may add a reason string and user properties to its answers
+    ///
+    /// Servers assume `true` when the property is absent. With `false` only the CONNACK, a
+    /// PUBLISH and a DISCONNECT may still carry them. The client disconnects with a protocol error
+    /// when any other packet does.
+    pub fn with_request_problem_information(&mut self, request: bool) -> &mut Self {
+        self.properties
+            .with_request_problem_information(u8::from(request));
+        self
+    }
+
     pub fn properties_mut(&mut self) -> &mut crate::packets::connect::ConnectProperties {
         &mut self.properties
     }
+
+    /// The CONNECT packet that gets sent when connecting with this connector
+    pub fn build_connect_packet(&self) -> mqtt_format::v5::packets::connect::MConnect<'_> {
+        mqtt_format::v5::packets::connect::MConnect {
+            client_identifier: self.client_identifier.as_str(),
+            username: self.username.as_ref().map(AsRef::as_ref),
+            password: self.password.as_ref().map(AsRef::as_ref),
+            clean_start: self.clean_start.as_bool(),
+            will: self.will.as_ref().map(|w| w.as_ref()),
+            properties: self.properties.as_ref(),
+            keep_alive: self.keep_alive.as_u16(),
+        }
+    }
+}
+
+/// Whether a [`MqttClient`] currently has a connection to a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+#[derive(Debug)]
+pub enum DisconnectInfo {
+    /// The transport was closed by the remote side
+    TransportClosed,
+
+    /// An error occured while receiving from the transport
+    TransportError(MqttPacketCodecError),
+
+    /// A received packet could not be handled
+    PacketHandling,
+
+    /// Sending a packet took longer than the write timeout of the connector
+    WriteTimeout,
+
+    /// The server sent a packet it must not send, like a second CONNACK
+    ///
+    /// This includes a reason string or user properties the CONNECT did not request. The client
+    /// answered with a DISCONNECT with the 'Protocol Error' reason, or 'Topic Alias invalid' if the
+    /// server used a topic alias above the maximum of the CONNECT.
+    ProtocolError,
+
+    /// The server ended the connection with a DISCONNECT
+    ServerDisconnect(ServerDisconnect),
+}
+
+/// The DISCONNECT a server sent before closing the connection
+pub struct ServerDisconnect {
+    pub(crate) reason_code: DisconnectReasonCode,
+    pub(crate) properties: DisconnectPropertiesView,
+}
+
+impl ServerDisconnect {
+    pub fn reason_code(&self) -> DisconnectReasonCode {
+        self.reason_code
+    }
+
+    /// The properties of the DISCONNECT, e.g. the server to use instead for 'Server moved'
+    pub fn properties(&self) -> &DisconnectPropertiesView {
+        &self.properties
+    }
+}
+
+impl std::fmt::Debug for ServerDisconnect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerDisconnect")
+            .field("reason_code", &self.reason_code)
+            .field("reason_string", &self.properties.reason_string())
+            .field("server_reference", &self.properties.server_reference())
+            .finish_non_exhaustive()
+    }
 }
 
 #[must_use]
 pub struct Connected {
     pub connack_prop_view: ConnackPropertiesView,
-    pub background_task: futures::future::BoxFuture<'static, Result<(), ()>>,
+    pub background_task: futures::future::BoxFuture<'static, DisconnectInfo>,
+    session_present: bool,
+    client: MqttClient,
+}
+
+impl Connected {
+    /// Whether the server resumed an existing session
+    ///
+    /// Without a resumed session the subscriptions of earlier connections are gone and need to be
+    /// made again.
+    pub fn session_present(&self) -> bool {
+        self.session_present
+    }
+
+    /// Drive the background task until the connection ends, returning why it ended
+    ///
+    /// With an `on_redirect` handler configured, a DISCONNECT with 'Use another server' or
+    /// 'Server moved' makes the client connect to the referenced server and keep running on that
+    /// connection instead.
+    pub async fn run_until_disconnect(self) -> DisconnectInfo {
+        let Connected {
+            mut background_task,
+            client,
+            ..
+        } = self;
+
+        loop {
+            let disconnect_info = background_task.await;
+
+            let Some(connector) = client.redirect_connector(&disconnect_info).await else {
+                return disconnect_info;
+            };
+
+            match client.connect(connector).await {
+                Ok(connected) => background_task = connected.background_task,
+                Err(error) => {
+                    tracing::error!(%error, "Could not follow the redirect of the server");
+                    return disconnect_info;
+                }
+            }
+        }
+    }
 }
 
 impl MqttClient {
+    /// The keep alive in effect for the current connection
+    ///
+    /// This is the value the server assigned in its CONNACK, or the requested one if it did not
+    /// assign any. Returns `None` if the client is not connected.
+    pub async fn keep_alive(&self) -> Option<KeepAlive> {
+        self.inner
+            .lock()
+            .await
+            .connection_state
+            .as_ref()
+            .map(|conn_state| conn_state.keep_alive)
+    }
+
+    /// The connector to follow a redirecting DISCONNECT with, if the client is configured to
+    async fn redirect_connector(
+        &self,
+        disconnect_info: &DisconnectInfo,
+    ) -> Option<MqttClientConnector> {
+        let DisconnectInfo::ServerDisconnect(disconnect) = disconnect_info else {
+            return None;
+        };
+
+        if !matches!(
+            disconnect.reason_code(),
+            DisconnectReasonCode::UseAnotherServer | DisconnectReasonCode::ServerMoved
+        ) {
+            return None;
+        }
+
+        let server_reference = disconnect.properties().server_reference()?;
+        let inner = self.inner.lock().await;
+        let on_redirect = inner.default_handlers.on_redirect.as_ref()?;
+
+        tracing::info!(%server_reference, "Following redirect of the server");
+        on_redirect(server_reference)
+    }
+
+    /// Whether the client is currently connected, without sending anything to the server
+    pub async fn connection_state(&self) -> ConnectionStatus {
+        let inner = self.inner.lock().await;
+
+        if inner.connection_state.is_some() {
+            ConnectionStatus::Connected
+        } else if inner.connecting.load(std::sync::atomic::Ordering::Acquire) {
+            ConnectionStatus::Connecting
+        } else {
+            ConnectionStatus::Disconnected
+        }
+    }
+
+    /// The client identifier of the current session
+    ///
+    /// This is the identifier assigned by the server if the client connected without one.
+    pub async fn client_identifier(&self) -> Option<MqttString> {
+        self.inner
+            .lock()
+            .await
+            .session_state
+            .as_ref()
+            .map(|sess_state| sess_state.client_identifier.clone())
+    }
+
+    /// Seconds the server keeps the current session after the connection closed
+    ///
+    /// This is the interval the server confirmed, which can differ from the one of the connector.
+    /// Zero means the session ends together with the connection.
+    pub async fn session_expiry_interval(&self) -> Option<u32> {
+        self.inner
+            .lock()
+            .await
+            .session_state
+            .as_ref()
+            .map(|sess_state| sess_state.session_expiry_interval)
+    }
+
+    /// Gracefully end the current connection
+    ///
+    /// Sends a DISCONNECT with `reason` and closes the transport afterwards. Unless `reason` is
+    /// 'Disconnect with Will Message', the server discards the will of this connection.
+    pub async fn disconnect(self, reason: DisconnectReasonCode) -> Result<(), ()> {
+        let Some(mut conn_state) = self.inner.lock().await.connection_state.take() else {
+            tracing::error!("No connection state found");
+            return Err(());
+        };
+
+        conn_state
+            .conn_write
+            .send(mqtt_format::v5::packets::MqttPacket::Disconnect(
+                mqtt_format::v5::packets::disconnect::MDisconnect {
+                    reason_code: reason,
+                    properties: mqtt_format::v5::packets::disconnect::DisconnectProperties::new(),
+                },
+            ))
+            .await
+            .map_err(drop)?;
+
+        conn_state.conn_write.close().await.map_err(drop)
+    }
+
     pub async fn connect(
         &self,
         connector: MqttClientConnector,
+    ) -> Result<Connected, MqttClientConnectError> {
+        // Resets the flag even when this future gets dropped before it completes
+        let _connecting = {
+            let inner = self.inner.lock().await;
+            inner
+                .connecting
+                .store(true, std::sync::atomic::Ordering::Release);
+            ConnectingGuard(inner.connecting.clone())
+        };
+
+        self.establish_connection(connector).await
+    }
+
+    async fn establish_connection(
+        &self,
+        mut connector: MqttClientConnector,
     ) -> Result<Connected, MqttClientConnectError> {
         type Mcce = MqttClientConnectError;
 
+        if connector.protocol_level != ProtocolLevel::V5 {
+            return Err(Mcce::UnsupportedProtocolLevel(connector.protocol_level));
+        }
+
+        if let Some(maximum) = connector.maximum_connect_packet_size {
+            let size =
+                mqtt_format::v5::packets::MqttPacket::Connect(connector.build_connect_packet())
+                    .binary_size();
+            if size > maximum {
+                return Err(Mcce::ConnectPacketTooLarge { size, maximum });
+            }
+        }
+
         let inner_clone = self.inner.clone();
-        let mut inner = self.inner.lock().await;
-        let (read, write) = tokio::io::split(MqttConnection::from(connector.transport));
+        let transport = connector
+            .transport
+            .take()
+            .expect("A connector always holds its transport until it is consumed");
+        let (read, write) = tokio::io::split(MqttConnection::from(transport));
         let mut conn_write = FramedWrite::new(write, crate::codecs::MqttPacketCodec);
-        let mut conn_read = FramedRead::new(read, crate::codecs::MqttPacketCodec);
-
-        let conn_packet = mqtt_format::v5::packets::connect::MConnect {
-            client_identifier: connector.client_identifier.as_str(),
-            username: connector.username.as_ref().map(AsRef::as_ref),
-            password: connector.password.as_ref().map(AsRef::as_ref),
-            clean_start: connector.clean_start.as_bool(),
-            will: connector.will.as_ref().map(|w| w.as_ref()),
-            properties: connector.properties.as_ref(),
-            keep_alive: connector.keep_alive.as_u16(),
+        let mut conn_read = match connector.read_buffer_capacity {
+            Some(capacity) => {
+                FramedRead::with_capacity(read, crate::codecs::MqttPacketCodec, capacity)
+            }
+            None => FramedRead::new(read, crate::codecs::MqttPacketCodec),
         };
 
         conn_write
-            .send(mqtt_format::v5::packets::MqttPacket::Connect(conn_packet))
+            .send(mqtt_format::v5::packets::MqttPacket::Connect(
+                connector.build_connect_packet(),
+            ))
             .await
             .map_err(Mcce::Send)?;
 
-        let Some(maybe_connack) = conn_read.next().await else {
-            return Err(Mcce::TransportUnexpectedlyClosed);
-        };
+        let can_use_auth = connector.properties.authentication_method.is_some();
 
-        let maybe_connack = match maybe_connack {
-            Ok(maybe_connack) => maybe_connack,
-            Err(e) => {
-                return Err(Mcce::Receive(e));
-            }
-        };
+        let maybe_connack = loop {
+            let Some(maybe_connack) = conn_read.next().await else {
+                return Err(Mcce::TransportUnexpectedlyClosed);
+            };
+
+            let maybe_connack = match maybe_connack {
+                Ok(maybe_connack) => maybe_connack,
+                Err(e) => {
+                    return Err(Mcce::Receive(e));
+                }
+            };
 
-        let connack = loop {
-            let can_use_auth = connector.properties.authentication_data.is_some();
-            let _auth = match maybe_connack.get() {
-                mqtt_format::v5::packets::MqttPacket::Connack(connack) => break connack,
+            let auth = match maybe_connack.get() {
+                mqtt_format::v5::packets::MqttPacket::Connack(_) => break maybe_connack,
                 mqtt_format::v5::packets::MqttPacket::Auth(auth) => {
                     if can_use_auth {
                         auth
@@ -201,13 +582,48 @@ pub async fn connect(
                 }
             };
 
-            // TODO: Use user-provided method to authenticate further
+            let method = connector
+                .properties
+                .authentication_method
+                .clone()
+                .unwrap_or_default();
+            let data = {
+                let inner = self.inner.lock().await;
+                let Some(on_auth_continue) = inner.default_handlers.on_auth_continue.as_ref()
+                else {
+                    return Err(Mcce::NoAuthHandler);
+                };
+
+                on_auth_continue(
+                    &method,
+                    auth.properties.authentication_data().map(|data| data.0),
+                )
+            };
+
+            let mut properties = crate::packets::auth::AuthProperties::new();
+            properties
+                .with_authentication_method(method)
+                .with_authentication_data(data);
+
+            conn_write
+                .send(mqtt_format::v5::packets::MqttPacket::Auth(
+                    mqtt_format::v5::packets::auth::MAuth {
+                        reason:
+                            mqtt_format::v5::packets::auth::AuthReasonCode::ContinueAuthentication,
+                        properties: properties.as_ref(),
+                    },
+                ))
+                .await
+                .map_err(Mcce::Send)?;
+        };
 
-            todo!()
+        let mqtt_format::v5::packets::MqttPacket::Connack(connack) = maybe_connack.get() else {
+            unreachable!("The loop above only ever breaks on a CONNACK")
         };
 
         // TODO: Timeout here if the server doesn't respond
 
+        let mut inner = self.inner.lock().await;
         if connack.reason_code == mqtt_format::v5::packets::connack::ConnackReasonCode::Success {
             // TODO: Read properties, configure client
 
@@ -218,7 +634,9 @@ pub async fn connect(
             }
 
             let (sender, heartbeat_receiver) = futures::channel::mpsc::channel(1);
-            let conn_write = TransportWriter::new(conn_write, sender);
+            let (teardown_sender, teardown_receiver) = futures::channel::oneshot::channel();
+            let conn_write =
+                TransportWriter::new(conn_write, sender, connector.write_timeout, teardown_sender);
 
             let (conn_read_sender, conn_read_recv) = futures::channel::oneshot::channel();
 
@@ -229,6 +647,15 @@ pub async fn connect(
                 retain_available: connack.properties.retain_available().map(|ra| ra.0),
                 maximum_packet_size: connack.properties.maximum_packet_size().map(|mps| mps.0),
                 topic_alias_maximum: connack.properties.topic_alias_maximum().map(|tam| tam.0),
+                advertised_topic_alias_maximum: connector
+                    .properties
+                    .topic_alias_maximum
+                    .unwrap_or(0),
+                requested_problem_information: connector
+                    .properties
+                    .request_problem_information
+                    .map_or(true, |rpi| rpi != 0),
+                authentication_method: connector.properties.authentication_method.clone(),
                 keep_alive: connack
                     .properties
                     .server_keep_alive()
@@ -240,7 +667,7 @@ pub async fn connect(
                     .unwrap_or(connector.keep_alive),
                 conn_write,
                 conn_read_recv,
-                next_packet_identifier: std::num::NonZeroU16::MIN,
+                packet_identifiers: connector.packet_identifier_allocator,
             };
 
             let assigned_client_identifier = connack.properties.assigned_client_identifier();
@@ -248,6 +675,10 @@ pub async fn connect(
             let client_identifier: MqttString;
 
             if let Some(aci) = assigned_client_identifier {
+                if connector.require_client_provided_id {
+                    return Err(MqttClientConnectError::ServerAssignedClientIdentifier);
+                }
+
                 if connector.client_identifier
                     == ProposedClientIdentifier::PotentiallyServerProvided
                 {
@@ -273,13 +704,48 @@ pub async fn connect(
                 };
             }
 
+            // A resumed session continues the QoS 2 publishes it received, the server follows
+            // up on them with a PUBREL. The server also kept the subscriptions.
+            let (outstanding_packets, awaiting_pubrel, subscriptions) =
+                match inner.session_state.take() {
+                    Some(previous)
+                        if connect_client_state.session_present
+                            && previous.client_identifier == client_identifier =>
+                    {
+                        (
+                            previous.outstanding_packets,
+                            previous.awaiting_pubrel,
+                            previous.subscriptions,
+                        )
+                    }
+                    _ => (
+                        OutstandingPackets::empty(),
+                        Default::default(),
+                        Default::default(),
+                    ),
+                };
+
+            // The server may override the interval the client asked for, which defaults to zero
+            let session_expiry_interval = connack
+                .properties
+                .session_expiry_interval()
+                .map(|sei| sei.0)
+                .or(connector.properties.session_expiry_interval)
+                .unwrap_or(0);
+
             let keep_alive = connect_client_state.keep_alive;
+            let session_present = connect_client_state.session_present;
 
             inner.connection_state = Some(connect_client_state);
             inner.session_state = Some(SessionState {
                 client_identifier,
-                outstanding_packets: OutstandingPackets::empty(),
+                outstanding_packets,
+                awaiting_pubrel,
+                session_expiry_interval,
+                subscriptions,
             });
+            resend_outstanding_packets(&mut inner).await?;
+            inner.emit_event(ClientEvent::Connected);
 
             let connack_prop_view =
                 crate::packets::connack::ConnackPropertiesView::try_from(maybe_connack)
@@ -291,6 +757,7 @@ pub async fn connect(
                     receiving_inner,
                     conn_read,
                     conn_read_sender,
+                    teardown_receiver,
                 );
 
                 let heartbeat_inner = inner_clone;
@@ -309,15 +776,22 @@ pub async fn connect(
                     futures::future::ok(()).right_future()
                 };
 
-                tokio::try_join!(receiving, heartbeat)
-                    .map(drop)
-                    .map_err(drop)
+                // The heartbeat only ends once the writer is gone, the connection lives as long as
+                // we are receiving
+                match futures::future::select(receiving.boxed(), heartbeat.boxed()).await {
+                    futures::future::Either::Left((disconnect_info, _heartbeat)) => disconnect_info,
+                    futures::future::Either::Right((_, receiving)) => receiving.await,
+                }
             }
             .boxed();
 
             return Ok(Connected {
                 connack_prop_view,
                 background_task,
+                session_present,
+                client: MqttClient {
+                    inner: self.inner.clone(),
+                },
             });
         }
 
@@ -327,6 +801,51 @@ pub async fn connect(
     }
 }
 
+/// Resend the PUBLISH and PUBREL packets a resumed session still has outstanding
+///
+/// MQTT-4.4.0-1 only allows this right after reconnecting, never on a live connection.
+async fn resend_outstanding_packets(
+    inner: &mut super::InnerClient,
+) -> Result<(), MqttClientConnectError> {
+    let (Some(conn_state), Some(sess_state)) = (
+        inner.connection_state.as_mut(),
+        inner.session_state.as_ref(),
+    ) else {
+        return Ok(());
+    };
+
+    for (ident, packet) in sess_state.outstanding_packets.iter_in_send_order() {
+        let resent = match packet.get() {
+            mqtt_format::v5::packets::MqttPacket::Publish(publish) => {
+                mqtt_format::v5::packets::MqttPacket::Publish(
+                    mqtt_format::v5::packets::publish::MPublish {
+                        duplicate: true,
+                        ..publish.clone()
+                    },
+                )
+            }
+            other => other.clone(),
+        };
+
+        tracing::debug!(%ident, "Resending unacknowledged packet of the resumed session");
+        conn_state
+            .conn_write
+            .send(resent)
+            .await
+            .map_err(MqttClientConnectError::Send)?;
+    }
+
+    Ok(())
+}
+
+struct ConnectingGuard(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl Drop for ConnectingGuard {
+    fn drop(&mut self) {
+        self.0.store(false, std::sync::atomic::Ordering::Release);
+    }
+}
+
 async fn handle_heartbeats(
     mut heartbeat_receiver: futures::channel::mpsc::Receiver<()>,
     duration: Duration,
@@ -344,16 +863,947 @@ async fn handle_heartbeats(
             _ = timeout => {
                 let mut inner = heartbeat_inner.lock().await;
                 let inner = &mut *inner;
+                // A disconnect takes the connection state before it is done sending the DISCONNECT
                 let Some(conn_state) = inner.connection_state.as_mut() else {
-                    todo!();
+                    tracing::debug!("Connection is going away, stopping the keep alive");
+                    break;
                 };
 
                 // We make sure that this won't deadlock in the send method
                 conn_state.conn_write.send(
                     mqtt_format::v5::packets::MqttPacket::Pingreq(mqtt_format::v5::packets::pingreq::MPingreq)
-                ).await.unwrap();
+                ).await.map_err(|error| tracing::error!(%error, "Could not send PINGREQ"))?;
+                inner.emit_event(ClientEvent::PingSent);
             }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use futures::StreamExt;
+    use mqtt_format::v5::packets::connack::ConnackProperties;
+    use mqtt_format::v5::packets::connack::ConnackReasonCode;
+    use mqtt_format::v5::packets::connack::MConnack;
+    use mqtt_format::v5::packets::disconnect::DisconnectProperties;
+    use mqtt_format::v5::packets::disconnect::DisconnectReasonCode;
+    use mqtt_format::v5::packets::disconnect::MDisconnect;
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::puback::MPuback;
+    use mqtt_format::v5::packets::puback::PubackProperties;
+    use mqtt_format::v5::packets::puback::PubackReasonCode;
+    use mqtt_format::v5::packets::pubcomp::PubcompReasonCode;
+    use mqtt_format::v5::packets::publish::MPublish;
+    use mqtt_format::v5::packets::publish::PublishProperties;
+    use mqtt_format::v5::packets::pubrel::MPubrel;
+    use mqtt_format::v5::packets::pubrel::PubrelProperties;
+    use mqtt_format::v5::packets::pubrel::PubrelReasonCode;
+    use mqtt_format::v5::packets::suback::MSuback;
+    use mqtt_format::v5::packets::suback::SubackProperties;
+    use mqtt_format::v5::packets::suback::SubackReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::QualityOfService;
+    use mqtt_format::v5::variable_header::AssignedClientIdentifier;
+    use mqtt_format::v5::variable_header::PacketIdentifier;
+    use mqtt_format::v5::variable_header::RequestProblemInformation;
+    use mqtt_format::v5::variable_header::RequestResponseInformation;
+    use mqtt_format::v5::variable_header::ResponseInformation;
+    use mqtt_format::v5::variable_header::ServerKeepAlive;
+    use mqtt_format::v5::variable_header::ServerReference;
+    use mqtt_format::v5::variable_header::SessionExpiryInterval;
+
+    use super::CleanStart;
+    use super::ConnectionStatus;
+    use super::DisconnectInfo;
+    use super::MqttClientConnectError;
+    use super::MqttClientConnector;
+    use super::MqttWill;
+    use super::ProtocolLevel;
+    use crate::client::MqttClient;
+    use crate::client_identifier::ProposedClientIdentifier;
+    use crate::codecs::MqttPacketCodecError;
+    use crate::keep_alive::KeepAlive;
+
+    #[tokio::test]
+    async fn run_until_disconnect_on_transport_close() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let connected = connected.unwrap();
+
+        drop(server);
+
+        assert!(matches!(
+            connected.run_until_disconnect().await,
+            DisconnectInfo::TransportClosed
+        ));
+    }
+
+    #[tokio::test]
+    async fn connection_state_follows_connection() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        assert_eq!(
+            client.connection_state().await,
+            ConnectionStatus::Disconnected
+        );
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            async {
+                let connect = server.receive().await;
+                assert!(matches!(connect.get(), FormatMqttPacket::Connect(_)));
+                assert_eq!(
+                    client.connection_state().await,
+                    ConnectionStatus::Connecting
+                );
+
+                server
+                    .send(FormatMqttPacket::Connack(MConnack {
+                        session_present: false,
+                        reason_code: ConnackReasonCode::Success,
+                        properties: ConnackProperties::new(),
+                    }))
+                    .await;
+            }
+        );
+        let connected = connected.unwrap();
+        assert_eq!(client.connection_state().await, ConnectionStatus::Connected);
+
+        drop(server);
+        connected.run_until_disconnect().await;
+        assert_eq!(
+            client.connection_state().await,
+            ConnectionStatus::Disconnected
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_connect_is_disconnected() {
+        let (transport, _server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        // The server never answers the CONNECT
+        let result = tokio::time::timeout(
+            Duration::from_millis(100),
+            client.connect(crate::test::make_connector(transport)),
+        )
+        .await;
+        assert!(result.is_err());
+
+        assert_eq!(
+            client.connection_state().await,
+            ConnectionStatus::Disconnected
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_sends_normal_disconnection() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_will(
+            MqttWill::builder()
+                .topic("last/will".try_into().unwrap())
+                .payload(b"gone".to_vec().try_into().unwrap())
+                .qos(mqtt_format::v5::qos::QualityOfService::AtMostOnce)
+                .retain(false)
+                .build(),
+        );
+
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+
+        client
+            .disconnect(DisconnectReasonCode::NormalDisconnection)
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Disconnect(disconnect) = packet.get() else {
+            panic!("Expected a DISCONNECT, got: {packet:?}");
+        };
+        // Anything but 'Disconnect with Will Message' makes the server discard the will
+        assert_eq!(
+            disconnect.reason_code,
+            DisconnectReasonCode::NormalDisconnection
+        );
+        assert!(server.framed.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn adopts_server_assigned_client_identifier() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potentially_server_provided(),
+            CleanStart::No,
+            KeepAlive::Disabled,
+        );
+
+        let mut properties = ConnackProperties::new();
+        properties.assigned_client_identifier = Some(AssignedClientIdentifier("assigned"));
+        let (connected, connect) = tokio::join!(
+            client.connect(connector),
+            server.accept_connect_with(properties)
+        );
+        let _connected = connected.unwrap();
+
+        let FormatMqttPacket::Connect(connect) = connect.get() else {
+            unreachable!()
+        };
+        assert_eq!(connect.client_identifier, "");
+        assert!(!connect.clean_start);
+        assert_eq!(
+            client.client_identifier().await.unwrap().as_ref(),
+            "assigned"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_assigned_client_identifier_is_a_protocol_error() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potentially_server_provided(),
+            CleanStart::Yes,
+            KeepAlive::Disabled,
+        );
+
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+
+        assert!(matches!(
+            connected,
+            Err(MqttClientConnectError::ServerProtocolError {
+                reason: "MQTT-3.2.2.3.7"
+            })
+        ));
+        assert_eq!(client.client_identifier().await, None);
+    }
+
+    #[tokio::test]
+    async fn required_client_provided_id_rejects_assigned_id() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potentially_server_provided(),
+            CleanStart::Yes,
+            KeepAlive::Disabled,
+        );
+        connector.with_require_client_provided_id(true);
+
+        let mut properties = ConnackProperties::new();
+        properties.assigned_client_identifier = Some(AssignedClientIdentifier("assigned"));
+        let (connected, _) = tokio::join!(
+            client.connect(connector),
+            server.accept_connect_with(properties)
+        );
+
+        assert!(matches!(
+            connected,
+            Err(MqttClientConnectError::ServerAssignedClientIdentifier)
+        ));
+        assert_eq!(
+            client.connection_state().await,
+            ConnectionStatus::Disconnected
+        );
+    }
+
+    #[tokio::test]
+    async fn run_until_disconnect_exposes_server_disconnect() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let connected = connected.unwrap();
+
+        let mut properties = DisconnectProperties::new();
+        properties.server_reference = Some(ServerReference("other.example.com"));
+        server
+            .send(FormatMqttPacket::Disconnect(MDisconnect {
+                reason_code: DisconnectReasonCode::ServerMoved,
+                properties,
+            }))
+            .await;
+
+        let DisconnectInfo::ServerDisconnect(disconnect) = connected.run_until_disconnect().await
+        else {
+            panic!("Expected the server to disconnect");
+        };
+        assert_eq!(disconnect.reason_code(), DisconnectReasonCode::ServerMoved);
+        assert_eq!(
+            disconnect.properties().server_reference(),
+            Some("other.example.com")
+        );
+        assert_eq!(disconnect.properties().reason_string(), None);
+    }
+
+    #[tokio::test]
+    async fn run_until_disconnect_follows_redirect() {
+        let (first_transport, mut first_server) = crate::test::make_transport();
+        let (second_transport, mut second_server) = crate::test::make_transport();
+        let second_transport = std::sync::Mutex::new(Some(second_transport));
+        let client = MqttClient::builder()
+            .with_on_redirect(Box::new(move |server_reference| {
+                assert_eq!(server_reference, "second");
+                second_transport
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .map(crate::test::make_connector)
+            }))
+            .build()
+            .await
+            .unwrap();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(first_transport)),
+            first_server.accept_connect()
+        );
+        let running = tokio::spawn(connected.unwrap().run_until_disconnect());
+
+        let mut properties = DisconnectProperties::new();
+        properties.server_reference = Some(ServerReference("second"));
+        first_server
+            .send(FormatMqttPacket::Disconnect(MDisconnect {
+                reason_code: DisconnectReasonCode::UseAnotherServer,
+                properties,
+            }))
+            .await;
+
+        second_server.accept_connect().await;
+
+        // The client only answers once it runs on the second connection
+        second_server
+            .send(FormatMqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: QualityOfService::AtLeastOnce,
+                retain: false,
+                topic_name: "foo/bar",
+                packet_identifier: Some(PacketIdentifier(1.try_into().unwrap())),
+                properties: PublishProperties::new(),
+                payload: b"hello",
+            }))
+            .await;
+        let packet = second_server.receive().await;
+        assert!(matches!(packet.get(), FormatMqttPacket::Puback(_)));
+        assert_eq!(client.connection_state().await, ConnectionStatus::Connected);
+
+        drop(second_server);
+        assert!(matches!(
+            running.await.unwrap(),
+            DisconnectInfo::TransportClosed
+        ));
+    }
+
+    #[tokio::test]
+    async fn malformed_connack_is_reported_with_context() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            async {
+                server.receive().await;
+                // A CONNACK with reserved acknowledge flags and an unknown reason code
+                server.send_bytes(&[0x20, 0x03, 0xFE, 0xFF, 0x00]).await;
+            }
+        );
+
+        let Err(error) = connected else {
+            panic!("Expected the malformed CONNACK to fail connecting");
+        };
+        assert!(matches!(
+            error,
+            MqttClientConnectError::Receive(MqttPacketCodecError::Parsing(_))
+        ));
+        assert!(
+            error.to_string().contains("while awaiting CONNACK"),
+            "Unexpected error message: {error}"
+        );
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[tokio::test]
+    async fn v3_protocol_level_is_rejected_before_sending() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_protocol_level(ProtocolLevel::V3_1_1);
+
+        assert!(matches!(
+            client.connect(connector).await,
+            Err(MqttClientConnectError::UnsupportedProtocolLevel(
+                ProtocolLevel::V3_1_1
+            ))
+        ));
+
+        // The transport got dropped without a CONNECT being written
+        assert!(server.framed.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_will_is_rejected_before_sending() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector
+            .with_will(
+                MqttWill::builder()
+                    .topic("last/words".try_into().unwrap())
+                    .payload(vec![0; 2048].try_into().unwrap())
+                    .qos(QualityOfService::AtMostOnce)
+                    .retain(false)
+                    .build(),
+            )
+            .with_maximum_connect_packet_size(1024);
+
+        let Err(MqttClientConnectError::ConnectPacketTooLarge { size, maximum }) =
+            client.connect(connector).await
+        else {
+            panic!("Expected the CONNECT to be too large");
+        };
+        assert!(size > 2048);
+        assert_eq!(maximum, 1024);
+
+        // Nothing was sent to the server
+        assert!(server.framed.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn connack_exposes_requested_response_information() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_request_response_information(true);
+
+        let mut properties = ConnackProperties::new();
+        properties.response_information = Some(ResponseInformation("responses/test"));
+        let (connected, connect) = tokio::join!(
+            client.connect(connector),
+            server.accept_connect_with(properties)
+        );
+
+        let FormatMqttPacket::Connect(connect) = connect.get() else {
+            unreachable!("accept_connect_with only accepts CONNECT packets")
+        };
+        assert_eq!(
+            connect.properties.request_response_information(),
+            Some(&RequestResponseInformation(1))
+        );
+        assert_eq!(
+            connected.unwrap().connack_prop_view.response_information(),
+            Some("responses/test")
+        );
+    }
+
+    #[tokio::test]
+    async fn session_expiry_interval_is_sent_and_tracked() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_session_expiry_interval(300);
+
+        let (connected, connect) = tokio::join!(client.connect(connector), server.accept_connect());
+        let _connected = connected.unwrap();
+
+        let FormatMqttPacket::Connect(connect) = connect.get() else {
+            unreachable!("accept_connect only accepts CONNECT packets")
+        };
+        assert_eq!(
+            connect.properties.session_expiry_interval(),
+            Some(&SessionExpiryInterval(300))
+        );
+        assert_eq!(client.session_expiry_interval().await, Some(300));
+    }
+
+    #[tokio::test]
+    async fn server_overrides_session_expiry_interval() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_session_expiry_interval(300);
+
+        let mut properties = ConnackProperties::new();
+        properties.session_expiry_interval = Some(SessionExpiryInterval(60));
+        let (connected, _) = tokio::join!(
+            client.connect(connector),
+            server.accept_connect_with(properties)
+        );
+        let _connected = connected.unwrap();
+
+        assert_eq!(client.session_expiry_interval().await, Some(60));
+    }
+
+    #[test]
+    fn connect_carries_request_problem_information() {
+        let (transport, _server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        assert_eq!(
+            connector
+                .build_connect_packet()
+                .properties
+                .request_problem_information(),
+            None
+        );
+
+        connector.with_request_problem_information(false);
+        assert_eq!(
+            connector
+                .build_connect_packet()
+                .properties
+                .request_problem_information(),
+            Some(&RequestProblemInformation(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn run_until_disconnect_on_transport_error() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let connected = connected.unwrap();
+
+        // Packet type 0 is reserved and thus invalid
+        server.send_bytes(&[0x00, 0x00]).await;
+
+        assert!(matches!(
+            connected.run_until_disconnect().await,
+            DisconnectInfo::TransportError(MqttPacketCodecError::Parsing(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn connected_reports_whether_the_session_was_resumed() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        assert!(!connected.unwrap().session_present());
+
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.clean_start = CleanStart::No;
+        let (connected, _) = tokio::join!(client.connect(connector), async {
+            server.receive().await;
+            server
+                .send(FormatMqttPacket::Connack(MConnack {
+                    session_present: true,
+                    reason_code: ConnackReasonCode::Success,
+                    properties: ConnackProperties::new(),
+                }))
+                .await;
+        });
+        assert!(connected.unwrap().session_present());
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_publish_is_resent_with_dup_after_resume() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let published = client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: crate::qos::QualityOfService::AtLeastOnce,
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(first) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert!(!first.duplicate);
+        let packet_identifier = first.packet_identifier;
+
+        // The connection drops before the PUBACK arrived
+        drop(server);
+        assert!(matches!(
+            published.acknowledged().await,
+            Err(crate::client::send::PublishFailed::ConnectionLost)
+        ));
+
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.clean_start = CleanStart::No;
+        let (connected, _) = tokio::join!(client.connect(connector), async {
+            server.receive().await;
+            server
+                .send(FormatMqttPacket::Connack(MConnack {
+                    session_present: true,
+                    reason_code: ConnackReasonCode::Success,
+                    properties: ConnackProperties::new(),
+                }))
+                .await;
+        });
+        tokio::spawn(connected.unwrap().background_task);
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(resent) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert!(resent.duplicate);
+        assert_eq!(resent.packet_identifier, packet_identifier);
+        assert_eq!(resent.payload, b"hello");
+
+        server
+            .send(FormatMqttPacket::Puback(MPuback {
+                packet_identifier: packet_identifier.unwrap(),
+                reason: PubackReasonCode::Success,
+                properties: PubackProperties::new(),
+            }))
+            .await;
+        while !client.outstanding_packet_ids().await.is_empty() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn resumed_session_keeps_subscriptions() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let subscriptions = [(
+            "foo/#".try_into().unwrap(),
+            crate::qos::QualityOfService::AtLeastOnce,
+        )];
+        let (subscribed, _) = tokio::join!(client.subscribe(&subscriptions), async {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Subscribe(subscribe) = packet.get() else {
+                panic!("Expected a SUBSCRIBE, got: {packet:?}");
+            };
+            server
+                .send(FormatMqttPacket::Suback(MSuback {
+                    packet_identifier: subscribe.packet_identifier,
+                    properties: SubackProperties::new(),
+                    reasons: &[SubackReasonCode::GrantedQoS1],
+                }))
+                .await;
+        });
+        subscribed.unwrap();
+        drop(server);
+        while client.connection_state().await != ConnectionStatus::Disconnected {
+            tokio::task::yield_now().await;
+        }
+
+        let reconnect = |session_present| {
+            let (transport, mut server) = crate::test::make_transport();
+            let mut connector = crate::test::make_connector(transport);
+            connector.clean_start = CleanStart::No;
+            let connected = client.connect(connector);
+            async move {
+                let (connected, _) = tokio::join!(connected, async {
+                    server.receive().await;
+                    server
+                        .send(FormatMqttPacket::Connack(MConnack {
+                            session_present,
+                            reason_code: ConnackReasonCode::Success,
+                            properties: ConnackProperties::new(),
+                        }))
+                        .await;
+                });
+                let _connected = connected.unwrap();
+            }
+        };
+
+        reconnect(true).await;
+        assert_eq!(client.active_subscriptions().await, subscriptions.to_vec());
+
+        // Without a resumed session the server forgot them as well
+        reconnect(false).await;
+        assert!(client.active_subscriptions().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resumed_session_completes_qos2_receive() {
+        let client = MqttClient::new_with_default_handlers();
+        let _incoming = client.incoming_publishes(1).await;
+        let mut server = crate::test::connect(&client).await;
+        let packet_identifier = PacketIdentifier(std::num::NonZeroU16::MIN);
+
+        server
+            .send(FormatMqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: QualityOfService::ExactlyOnce,
+                retain: false,
+                topic_name: "foo/bar",
+                packet_identifier: Some(packet_identifier),
+                properties: PublishProperties::new(),
+                payload: b"hello",
+            }))
+            .await;
+        let pubrec = server.receive().await;
+        assert!(matches!(pubrec.get(), FormatMqttPacket::Pubrec(_)));
+        // The connection drops before the server sent its PUBREL
+        drop(server);
+        while client.connection_state().await != ConnectionStatus::Disconnected {
+            tokio::task::yield_now().await;
+        }
+
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.clean_start = CleanStart::No;
+        let (connected, _) = tokio::join!(client.connect(connector), async {
+            server.receive().await;
+            server
+                .send(FormatMqttPacket::Connack(MConnack {
+                    session_present: true,
+                    reason_code: ConnackReasonCode::Success,
+                    properties: ConnackProperties::new(),
+                }))
+                .await;
+        });
+        tokio::spawn(connected.unwrap().background_task);
+
+        server
+            .send(FormatMqttPacket::Pubrel(MPubrel {
+                packet_identifier,
+                reason: PubrelReasonCode::Success,
+                properties: PubrelProperties::new(),
+            }))
+            .await;
+        let packet = server.receive().await;
+        let FormatMqttPacket::Pubcomp(pubcomp) = packet.get() else {
+            panic!("Expected a PUBCOMP, got: {packet:?}");
+        };
+        assert_eq!(pubcomp.reason, PubcompReasonCode::Success);
+    }
+
+    #[tokio::test]
+    async fn receives_publish_larger_than_read_buffer_capacity() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_read_buffer_capacity(64 * 1024);
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+        let mut incoming = client.incoming_publishes(1).await;
+
+        let payload = vec![0xAB; 100 * 1024];
+        server
+            .send(FormatMqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: QualityOfService::AtMostOnce,
+                retain: false,
+                topic_name: "foo/bar",
+                packet_identifier: None,
+                properties: PublishProperties::new(),
+                payload: &payload,
+            }))
+            .await;
+
+        assert_eq!(incoming.next().await.unwrap().payload(), payload);
+    }
+
+    #[tokio::test]
+    async fn read_buffer_capacity_avoids_reallocations() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_read_buffer_capacity(256 * 1024);
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+        let mut incoming = client.incoming_publishes(1).await;
+
+        let payload = vec![0xAB; 100 * 1024];
+        let publish = FormatMqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtMostOnce,
+            retain: false,
+            topic_name: "foo/bar",
+            packet_identifier: None,
+            properties: PublishProperties::new(),
+            payload: &payload,
+        });
+        let connack = FormatMqttPacket::Connack(MConnack {
+            session_present: false,
+            reason_code: ConnackReasonCode::Success,
+            properties: ConnackProperties::new(),
+        });
+        server.send(publish.clone()).await;
+
+        // Keep the publish alive, so the buffer cannot reclaim the memory it was read into
+        let received = incoming.next().await.unwrap();
+        assert_eq!(received.payload(), payload);
+
+        let conn_state = client.inner.lock().await.connection_state.take().unwrap();
+        drop(server);
+        let reader = conn_state.conn_read_recv.await.unwrap();
+
+        // Both packets were split off the initial allocation, it never had to grow
+        let consumed = connack.binary_size() + publish.binary_size();
+        assert_eq!(
+            reader.read_buffer().capacity() + consumed as usize,
+            256 * 1024
+        );
+    }
+
+    #[tokio::test]
+    async fn heartbeat_stops_while_disconnecting() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potetially_accepted("test").unwrap(),
+            super::CleanStart::Yes,
+            KeepAlive::Seconds(1.try_into().unwrap()),
+        );
+
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        let background_task = tokio::spawn(connected.unwrap().background_task);
+
+        // Like `disconnect` does while the DISCONNECT is still being sent
+        let conn_state = client.inner.lock().await.connection_state.take().unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        drop(conn_state);
+        drop(server);
+        assert!(matches!(
+            background_task.await.unwrap(),
+            DisconnectInfo::TransportClosed
+        ));
+    }
+
+    #[tokio::test]
+    async fn pings_with_server_assigned_keep_alive() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potetially_accepted("test").unwrap(),
+            super::CleanStart::Yes,
+            KeepAlive::Seconds(60.try_into().unwrap()),
+        );
+
+        let mut properties = ConnackProperties::new();
+        properties.server_keep_alive = Some(ServerKeepAlive(1));
+        let (connected, _) = tokio::join!(
+            client.connect(connector),
+            server.accept_connect_with(properties)
+        );
+        tokio::spawn(connected.unwrap().background_task);
+
+        assert_eq!(
+            client.keep_alive().await,
+            Some(KeepAlive::Seconds(1.try_into().unwrap()))
+        );
+
+        let start = Instant::now();
+        for _ in 0..2 {
+            let packet = server.receive().await;
+            assert!(
+                matches!(packet.get(), FormatMqttPacket::Pingreq(_)),
+                "Expected a PINGREQ, got: {packet:?}"
+            );
+            server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+        }
+
+        // Both pings are due well before the requested keep alive of a minute
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn build_connect_packet_reflects_connector() {
+        let (transport, _server) = crate::test::make_transport();
+        let mut connector = MqttClientConnector::new(
+            transport,
+            ProposedClientIdentifier::new_potetially_accepted("test").unwrap(),
+            CleanStart::No,
+            KeepAlive::Seconds(30.try_into().unwrap()),
+        );
+        connector
+            .with_username("user".try_into().unwrap())
+            .with_password(b"secret".to_vec().try_into().unwrap())
+            .with_will(
+                MqttWill::builder()
+                    .topic("last/will".try_into().unwrap())
+                    .payload(b"gone".to_vec().try_into().unwrap())
+                    .qos(mqtt_format::v5::qos::QualityOfService::AtLeastOnce)
+                    .retain(true)
+                    .build(),
+            )
+            .properties_mut()
+            .with_session_expiry_interval(120);
+
+        let packet = connector.build_connect_packet();
+
+        assert_eq!(packet.client_identifier, "test");
+        assert!(!packet.clean_start);
+        assert_eq!(packet.keep_alive, 30);
+        assert_eq!(packet.username, Some("user"));
+        assert_eq!(packet.password, Some(&b"secret"[..]));
+        let will = packet.will.unwrap();
+        assert_eq!(will.topic, "last/will");
+        assert_eq!(will.payload, b"gone");
+        assert_eq!(
+            will.will_qos,
+            mqtt_format::v5::qos::QualityOfService::AtLeastOnce
+        );
+        assert!(will.will_retain);
+        assert_eq!(
+            packet.properties.session_expiry_interval(),
+            Some(&mqtt_format::v5::variable_header::SessionExpiryInterval(
+                120
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn will_properties_are_sent_with_the_connect() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut will = MqttWill::builder()
+            .topic("last/will".try_into().unwrap())
+            .payload(b"{}".to_vec().try_into().unwrap())
+            .qos(QualityOfService::AtMostOnce)
+            .retain(false)
+            .build();
+        will.get_properties_mut()
+            .with_will_delay_interval(10)
+            .with_content_type("application/json".to_string())
+            .with_correlation_data(b"abc".to_vec());
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_will(will);
+
+        let (connected, connect) = tokio::join!(client.connect(connector), server.accept_connect());
+        let _connected = connected.unwrap();
+
+        let FormatMqttPacket::Connect(connect) = connect.get() else {
+            panic!("Expected a CONNECT, got: {connect:?}");
+        };
+        let properties = &connect.will.as_ref().unwrap().properties;
+        assert_eq!(properties.will_delay_interval().map(|wdi| wdi.0), Some(10));
+        assert_eq!(
+            properties.content_type().map(|ct| ct.0),
+            Some("application/json")
+        );
+        assert_eq!(
+            properties.correlation_data().map(|cd| cd.0),
+            Some(&b"abc"[..])
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+//! Lifecycle events of the connections of a [`MqttClient`]
+
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::StreamExt;
+use mqtt_format::v5::packets::disconnect::DisconnectReasonCode;
+
+use super::connect::DisconnectInfo;
+use super::InnerClient;
+use super::MqttClient;
+
+/// Something that happened on the connection of a [`MqttClient`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientEvent {
+    /// The server accepted a connection
+    Connected,
+
+    /// The connection ended, the background task returns the full [`DisconnectInfo`]
+    Disconnected { reason: DisconnectReason },
+
+    /// A PINGREQ was sent, either explicitly or because of the keep alive
+    PingSent,
+
+    /// The server answered with a PINGRESP
+    PingAcked,
+
+    /// The server sent a PUBLISH
+    MessageReceived,
+}
+
+/// Why a connection ended, a copyable summary of [`DisconnectInfo`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisconnectReason {
+    TransportClosed,
+    TransportError,
+    PacketHandling,
+    WriteTimeout,
+    ProtocolError,
+    ServerDisconnect(DisconnectReasonCode),
+}
+
+impl From<&DisconnectInfo> for DisconnectReason {
+    fn from(info: &DisconnectInfo) -> Self {
+        match info {
+            DisconnectInfo::TransportClosed => DisconnectReason::TransportClosed,
+            DisconnectInfo::TransportError(_) => DisconnectReason::TransportError,
+            DisconnectInfo::PacketHandling => DisconnectReason::PacketHandling,
+            DisconnectInfo::WriteTimeout => DisconnectReason::WriteTimeout,
+            DisconnectInfo::ProtocolError => DisconnectReason::ProtocolError,
+            DisconnectInfo::ServerDisconnect(disconnect) => {
+                DisconnectReason::ServerDisconnect(disconnect.reason_code())
+            }
+        }
+    }
+}
+
+impl MqttClient {
+    /// Observe the lifecycle of the connections of this client
+    ///
+    /// At most `capacity` events are buffered (but always at least one), further events are
+    /// dropped until the stream catches up. Calling this again replaces the previous stream.
+    pub async fn events(&self, capacity: usize) -> ClientEvents {
+        let (sender, recv) = futures::channel::mpsc::channel(capacity.saturating_sub(1));
+
+        self.inner.lock().await.events = Some(sender);
+
+        ClientEvents { recv }
+    }
+}
+
+impl InnerClient {
+    pub(super) fn emit_event(&mut self, event: ClientEvent) {
+        let Some(events) = self.events.as_mut() else {
+            return;
+        };
+
+        if let Err(error) = events.try_send(event) {
+            tracing::debug!(
+                ?event,
+                full = error.is_full(),
+                "Could not emit client event"
+            );
+        }
+    }
+}
+
+/// The stream of [`ClientEvent`]s, see [`MqttClient::events`]
+pub struct ClientEvents {
+    recv: futures::channel::mpsc::Receiver<ClientEvent>,
+}
+
+impl futures::Stream for ClientEvents {
+    type Item = ClientEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.recv.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+
+    use super::ClientEvent;
+    use super::DisconnectReason;
+    use crate::client::MqttClient;
+
+    #[tokio::test]
+    async fn events_follow_the_connection_lifecycle() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut events = client.events(8).await;
+        let mut server = crate::test::connect(&client).await;
+
+        assert_eq!(events.next().await, Some(ClientEvent::Connected));
+
+        let ping = client.ping().await.unwrap();
+        assert_eq!(events.next().await, Some(ClientEvent::PingSent));
+        server.receive().await;
+        server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+        ping.response().await;
+        assert_eq!(events.next().await, Some(ClientEvent::PingAcked));
+
+        drop(server);
+        assert_eq!(
+            events.next().await,
+            Some(ClientEvent::Disconnected {
+                reason: DisconnectReason::TransportClosed
+            })
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::StreamExt;
+
+use super::MqttClient;
+use crate::packets::publish::Publish;
+
+/// Why a received message was not delivered to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedMessageReason {
+    /// The channel of incoming publishes is full
+    ChannelFull,
+
+    /// The receiving side of the incoming publishes was dropped
+    ReceiverDropped,
+
+    /// Nobody asked for the incoming publishes yet
+    NoReceiver,
+
+    /// The server sent a publish the client cannot process
+    ProtocolError,
+}
+
+impl MqttClient {
+    /// Receive the publishes sent by the server
+    ///
+    /// At most `capacity` publishes are buffered (but always at least one), once the buffer is
+    /// full further publishes are handed to the `on_dropped_message` handler instead.
+    /// Calling this again replaces the previous stream.
+    pub async fn incoming_publishes(&self, capacity: usize) -> IncomingPublishes {
+        let (sender, recv) = futures::channel::mpsc::channel(capacity.saturating_sub(1));
+
+        self.inner.lock().await.incoming_publishes = Some(sender);
+
+        IncomingPublishes { recv }
+    }
+}
+
+/// The publishes received from the server
+///
+/// All publishes go through this single stream in the order the server sent them, regardless of
+/// their quality of service or packet identifier. The MQTT spec requires this ordering for
+/// publishes of the same quality of service. Keep that in mind before fanning messages out to
+/// several tasks.
+pub struct IncomingPublishes {
+    recv: futures::channel::mpsc::Receiver<Publish>,
+}
+
+impl futures::Stream for IncomingPublishes {
+    type Item = Publish;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.recv.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use futures::StreamExt;
+    use mqtt_format::v5::packets::puback::PubackReasonCode;
+    use mqtt_format::v5::packets::pubcomp::PubcompReasonCode;
+    use mqtt_format::v5::packets::publish::MPublish;
+    use mqtt_format::v5::packets::publish::PublishProperties;
+    use mqtt_format::v5::packets::pubrec::PubrecReasonCode;
+    use mqtt_format::v5::packets::pubrel::MPubrel;
+    use mqtt_format::v5::packets::pubrel::PubrelProperties;
+    use mqtt_format::v5::packets::pubrel::PubrelReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::QualityOfService;
+    use mqtt_format::v5::variable_header::ContentType;
+    use mqtt_format::v5::variable_header::CorrelationData;
+    use mqtt_format::v5::variable_header::MessageExpiryInterval;
+    use mqtt_format::v5::variable_header::PacketIdentifier;
+    use mqtt_format::v5::variable_header::ResponseTopic;
+
+    use super::DroppedMessageReason;
+    use crate::client::MqttClient;
+
+    fn publish(packet_identifier: u16, payload: &[u8]) -> FormatMqttPacket<'_> {
+        FormatMqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtLeastOnce,
+            retain: false,
+            topic_name: "foo/bar",
+            packet_identifier: Some(PacketIdentifier(packet_identifier.try_into().unwrap())),
+            properties: PublishProperties::new(),
+            payload,
+        })
+    }
+
+    #[tokio::test]
+    async fn full_channel_calls_dropped_message_handler() {
+        let (dropped_send, mut dropped_recv) = futures::channel::mpsc::unbounded();
+        let client = MqttClient::builder()
+            .with_on_dropped_message(Box::new(move |packet, reason| {
+                dropped_send.unbounded_send((packet, reason)).unwrap();
+            }))
+            .build()
+            .await
+            .unwrap();
+        let mut server = crate::test::connect(&client).await;
+
+        let mut incoming = client.incoming_publishes(1).await;
+
+        server.send(publish(1, b"first")).await;
+        let packet = server.receive().await;
+        let FormatMqttPacket::Puback(puback) = packet.get() else {
+            panic!("Expected a PUBACK, got: {packet:?}");
+        };
+        assert_eq!(puback.reason, PubackReasonCode::Success);
+
+        server.send(publish(2, b"second")).await;
+        let (packet, reason) = dropped_recv.next().await.unwrap();
+        assert_eq!(reason, DroppedMessageReason::ChannelFull);
+        let FormatMqttPacket::Publish(dropped) = packet.get() else {
+            panic!("Expected the dropped PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(dropped.payload, b"second");
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Puback(puback) = packet.get() else {
+            panic!("Expected a PUBACK, got: {packet:?}");
+        };
+        assert_eq!(puback.reason, PubackReasonCode::UnspecifiedError);
+
+        let received = incoming.next().await.unwrap();
+        assert_eq!(received.topic(), "foo/bar");
+        assert_eq!(received.payload(), b"first");
+    }
+
+    #[tokio::test]
+    async fn publish_without_receiver_calls_dropped_message_handler() {
+        let (dropped_send, mut dropped_recv) = futures::channel::mpsc::unbounded();
+        let client = MqttClient::builder()
+            .with_on_dropped_message(Box::new(move |packet, reason| {
+                dropped_send.unbounded_send((packet, reason)).unwrap();
+            }))
+            .build()
+            .await
+            .unwrap();
+        let mut server = crate::test::connect(&client).await;
+
+        server.send(publish(1, b"unheard")).await;
+        let (packet, reason) = dropped_recv.next().await.unwrap();
+        assert_eq!(reason, DroppedMessageReason::NoReceiver);
+        let FormatMqttPacket::Publish(dropped) = packet.get() else {
+            panic!("Expected the dropped PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(dropped.payload, b"unheard");
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Puback(puback) = packet.get() else {
+            panic!("Expected a PUBACK, got: {packet:?}");
+        };
+        assert_eq!(puback.reason, PubackReasonCode::UnspecifiedError);
+    }
+
+    #[tokio::test]
+    async fn delivered_publish_exposes_its_properties() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+        let mut incoming = client.incoming_publishes(1).await;
+
+        server
+            .send(FormatMqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: QualityOfService::AtMostOnce,
+                retain: false,
+                topic_name: "foo/bar",
+                packet_identifier: None,
+                properties: PublishProperties {
+                    message_expiry_interval: Some(MessageExpiryInterval(30)),
+                    content_type: Some(ContentType("text/plain")),
+                    response_topic: Some(ResponseTopic("foo/response")),
+                    correlation_data: Some(CorrelationData(b"request-1")),
+                    ..PublishProperties::new()
+                },
+                payload: b"hello",
+            }))
+            .await;
+
+        let received = incoming.next().await.unwrap();
+        assert_eq!(received.message_expiry_interval(), Some(30));
+        assert_eq!(received.content_type(), Some("text/plain"));
+        assert_eq!(received.response_topic(), Some("foo/response"));
+        assert_eq!(received.correlation_data(), Some(&b"request-1"[..]));
+    }
+
+    #[tokio::test]
+    async fn qos1_publishes_are_delivered_in_order() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+        let mut incoming = client.incoming_publishes(3).await;
+
+        // Deliberately not in the order of their packet identifiers
+        let sent = [(3, &b"first"[..]), (1, b"second"), (2, b"third")];
+        for (packet_identifier, payload) in sent {
+            server.send(publish(packet_identifier, payload)).await;
+        }
+
+        for (packet_identifier, payload) in sent {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Puback(puback) = packet.get() else {
+                panic!("Expected a PUBACK, got: {packet:?}");
+            };
+            assert_eq!(puback.packet_identifier.0.get(), packet_identifier);
+
+            let received = incoming.next().await.unwrap();
+            assert_eq!(received.payload(), payload);
+        }
+    }
+
+    #[tokio::test]
+    async fn retransmitted_qos2_publish_is_delivered_once() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+        let mut incoming = client.incoming_publishes(2).await;
+
+        let publish = |duplicate| {
+            FormatMqttPacket::Publish(MPublish {
+                duplicate,
+                quality_of_service: QualityOfService::ExactlyOnce,
+                retain: false,
+                topic_name: "foo/bar",
+                packet_identifier: Some(PacketIdentifier(1.try_into().unwrap())),
+                properties: PublishProperties::new(),
+                payload: b"once",
+            })
+        };
+
+        // The server did not see the PUBREC and sends the publish again
+        for duplicate in [false, true] {
+            server.send(publish(duplicate)).await;
+            let packet = server.receive().await;
+            let FormatMqttPacket::Pubrec(pubrec) = packet.get() else {
+                panic!("Expected a PUBREC, got: {packet:?}");
+            };
+            assert_eq!(pubrec.reason, PubrecReasonCode::Success);
+        }
+
+        server
+            .send(FormatMqttPacket::Pubrel(MPubrel {
+                packet_identifier: PacketIdentifier(1.try_into().unwrap()),
+                reason: PubrelReasonCode::Success,
+                properties: PubrelProperties::new(),
+            }))
+            .await;
+        let packet = server.receive().await;
+        let FormatMqttPacket::Pubcomp(pubcomp) = packet.get() else {
+            panic!("Expected a PUBCOMP, got: {packet:?}");
+        };
+        assert_eq!(pubcomp.reason, PubcompReasonCode::Success);
+
+        assert_eq!(incoming.next().await.unwrap().payload(), b"once");
+        assert!(incoming.next().now_or_never().is_none());
+    }
+}
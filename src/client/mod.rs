@@ -4,12 +4,20 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod builder;
 pub mod connect;
+pub mod events;
+pub mod incoming;
 mod receive;
 pub mod send;
 mod state;
+pub mod subscribe;
+pub mod unsubscribe;
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use futures::lock::Mutex;
@@ -24,6 +32,10 @@ struct InnerClient {
     session_state: Option<SessionState>,
     default_handlers: ClientHandlers,
     outstanding_callbacks: Callbacks,
+    incoming_publishes: Option<futures::channel::mpsc::Sender<crate::packets::publish::Publish>>,
+    events: Option<futures::channel::mpsc::Sender<events::ClientEvent>>,
+    /// Set while a connection is being established
+    connecting: Arc<AtomicBool>,
 }
 
 pub struct MqttClient {
@@ -38,6 +50,9 @@ pub fn new_with_default_handlers() -> MqttClient {
                 session_state: None,
                 default_handlers: ClientHandlers::default(),
                 outstanding_callbacks: Callbacks::new(),
+                incoming_publishes: None,
+                events: None,
+                connecting: Arc::new(AtomicBool::new(false)),
             })),
         }
     }
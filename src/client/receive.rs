@@ -7,14 +7,27 @@
 use std::sync::Arc;
 
 use futures::lock::Mutex;
+use futures::FutureExt;
 use futures::StreamExt;
 use tokio_util::codec::FramedRead;
 use tracing::Instrument;
 use yoke::Yoke;
 
+use super::auth::ReauthenticationError;
+use super::connect::DisconnectInfo;
+use super::connect::ServerDisconnect;
+use super::events::ClientEvent;
+use super::events::DisconnectReason;
+use super::incoming::DroppedMessageReason;
+use super::send::pubrec_accepted;
+use super::subscribe::granted_qos;
+use super::subscribe::Subscribed;
+use super::unsubscribe::Unsubscribed;
 use super::InnerClient;
 use crate::codecs::MqttPacketCodec;
 use crate::packet_identifier::PacketIdentifier;
+use crate::packets::disconnect::DisconnectPropertiesView;
+use crate::packets::unsuback::UnsubackReasonCode;
 use crate::packets::MqttPacket;
 use crate::packets::MqttWriter;
 use crate::packets::StableBytes;
@@ -26,11 +39,30 @@ pub(super) async fn handle_background_receiving(
     conn_read_sender: futures::channel::oneshot::Sender<
         FramedRead<tokio::io::ReadHalf<MqttConnection>, MqttPacketCodec>,
     >,
-) -> Result<(), ()> {
+    teardown: futures::channel::oneshot::Receiver<()>,
+) -> DisconnectInfo {
     tracing::info!("Starting background task");
     let inner: Arc<Mutex<InnerClient>> = inner_clone;
 
-    while let Some(next) = conn_read.next().await {
+    let mut teardown = async move {
+        if teardown.await.is_err() {
+            // The writer went away without a timeout, only the transport ends the connection now
+            futures::future::pending::<()>().await;
+        }
+    }
+    .boxed();
+
+    let disconnect_info = loop {
+        let next = match futures::future::select(conn_read.next(), &mut teardown).await {
+            futures::future::Either::Left((next, _)) => next,
+            futures::future::Either::Right(((), _)) => {
+                tracing::error!("A write timed out, stopping");
+                break DisconnectInfo::WriteTimeout;
+            }
+        };
+        let Some(next) = next else {
+            break DisconnectInfo::TransportClosed;
+        };
         let process_span = tracing::debug_span!(
             "Processing packet",
             packet_kind = tracing::field::Empty,
@@ -39,60 +71,317 @@ pub(super) async fn handle_background_receiving(
         tracing::debug!(parent: &process_span, valid = next.is_ok(), "Received packet");
         let packet = match next {
             Ok(packet) => packet,
-            Err(e) => panic!("Received err: {e}"),
+            Err(e) => {
+                tracing::error!(parent: &process_span, error = %e, "Could not receive packet");
+                break DisconnectInfo::TransportError(e);
+            }
         };
         process_span.record(
             "packet_kind",
             tracing::field::debug(packet.get().get_kind()),
         );
+        if let Some(pident) = packet_identifier(packet.get()) {
+            process_span.record("packet_identifier", tracing::field::display(pident));
+        }
 
         tracing::trace!("Calling on_packet_recv() handler");
         (inner.lock().await.default_handlers.on_packet_recv)(packet.clone());
 
-        match packet.get() {
-            mqtt_format::v5::packets::MqttPacket::Auth(_) => todo!(),
-            mqtt_format::v5::packets::MqttPacket::Disconnect(_) => todo!(),
+        if carries_problem_information(packet.get()) && !requested_problem_information(&inner).await
+        {
+            tracing::error!(parent: &process_span, "Server sent problem information the client did not request, stopping");
+            disconnect_with_reason(
+                &inner,
+                mqtt_format::v5::packets::disconnect::DisconnectReasonCode::ProtocolError,
+            )
+            .instrument(process_span)
+            .await;
+            break DisconnectInfo::ProtocolError;
+        }
+
+        let handled = match packet.get() {
+            mqtt_format::v5::packets::MqttPacket::Auth(auth) => {
+                handle_auth(auth, &inner).instrument(process_span).await
+            }
+            mqtt_format::v5::packets::MqttPacket::Disconnect(disconnect) => {
+                tracing::info!(parent: &process_span, reason_code = ?disconnect.reason_code, "Server disconnected");
+                break DisconnectInfo::ServerDisconnect(ServerDisconnect {
+                    reason_code: disconnect.reason_code,
+                    properties: DisconnectPropertiesView::try_from(packet.clone())
+                        .expect("The packet was just matched as a DISCONNECT"),
+                });
+            }
             mqtt_format::v5::packets::MqttPacket::Pingreq(pingreq) => {
-                handle_pingreq(pingreq).instrument(process_span).await?
+                handle_pingreq(pingreq).instrument(process_span).await
             }
             mqtt_format::v5::packets::MqttPacket::Pingresp(pingresp) => {
                 handle_pingresp(pingresp, &inner)
                     .instrument(process_span)
-                    .await?
+                    .await
             }
             mqtt_format::v5::packets::MqttPacket::Puback(_mpuback) => {
                 handle_puback(&packet.try_into().unwrap(), &inner)
                     .instrument(process_span)
-                    .await?
+                    .await
             }
             mqtt_format::v5::packets::MqttPacket::Pubrec(pubrec) => {
                 handle_pubrec(pubrec, &inner, &packet)
                     .instrument(process_span)
-                    .await?
+                    .await
             }
             mqtt_format::v5::packets::MqttPacket::Pubcomp(pubcomp) => {
                 handle_pubcomp(pubcomp, &inner, &packet)
                     .instrument(process_span)
-                    .await?
+                    .await
+            }
+            mqtt_format::v5::packets::MqttPacket::Publish(publish) => {
+                if exceeds_topic_alias_maximum(publish, &inner).await {
+                    tracing::error!(parent: &process_span, "Server used a topic alias above the advertised maximum, stopping");
+                    disconnect_with_reason(
+                        &inner,
+                        mqtt_format::v5::packets::disconnect::DisconnectReasonCode::TopicAliasInvalid,
+                    )
+                    .instrument(process_span)
+                    .await;
+                    break DisconnectInfo::ProtocolError;
+                }
+
+                handle_publish(&packet, &inner)
+                    .instrument(process_span)
+                    .await
+            }
+            mqtt_format::v5::packets::MqttPacket::Pubrel(pubrel) => {
+                handle_pubrel(pubrel, &inner).instrument(process_span).await
+            }
+            mqtt_format::v5::packets::MqttPacket::Suback(suback) => {
+                handle_suback(suback, &inner).instrument(process_span).await
+            }
+            mqtt_format::v5::packets::MqttPacket::Unsuback(unsuback) => {
+                handle_unsuback(unsuback, &inner)
+                    .instrument(process_span)
+                    .await
             }
-            mqtt_format::v5::packets::MqttPacket::Publish(_) => todo!(),
-            mqtt_format::v5::packets::MqttPacket::Pubrel(_) => todo!(),
-            mqtt_format::v5::packets::MqttPacket::Suback(_) => todo!(),
-            mqtt_format::v5::packets::MqttPacket::Unsuback(_) => todo!(),
 
             mqtt_format::v5::packets::MqttPacket::Connack(_)
             | mqtt_format::v5::packets::MqttPacket::Connect(_)
             | mqtt_format::v5::packets::MqttPacket::Subscribe(_)
             | mqtt_format::v5::packets::MqttPacket::Unsubscribe(_) => {
-                todo!("Handle invalid packet")
+                // A CONNACK is only valid as the first packet, which the connector already read
+                tracing::error!(parent: &process_span, "Server sent a packet it must not send, stopping");
+                disconnect_with_reason(
+                    &inner,
+                    mqtt_format::v5::packets::disconnect::DisconnectReasonCode::ProtocolError,
+                )
+                .instrument(process_span)
+                .await;
+                break DisconnectInfo::ProtocolError;
             }
+        };
+
+        if handled.is_err() {
+            tracing::error!("Could not handle received packet, stopping");
+            break DisconnectInfo::PacketHandling;
         }
+    };
+
+    {
+        let mut inner = inner.lock().await;
+        inner.connection_state = None;
+        inner.outstanding_callbacks.abort_outstanding();
+        inner.emit_event(ClientEvent::Disconnected {
+            reason: DisconnectReason::from(&disconnect_info),
+        });
     }
 
-    tracing::debug!("Finished processing, returning reader");
+    tracing::debug!(?disconnect_info, "Finished processing, returning reader");
     if let Err(_conn_read) = conn_read_sender.send(conn_read) {
-        tracing::error!("Failed to return reader");
-        todo!()
+        tracing::debug!("Reader was not reclaimed, the connection state is already gone");
+    }
+
+    disconnect_info
+}
+
+fn packet_identifier(
+    packet: &mqtt_format::v5::packets::MqttPacket<'_>,
+) -> Option<PacketIdentifier> {
+    use mqtt_format::v5::packets::MqttPacket as Packet;
+
+    match packet {
+        Packet::Publish(publish) => publish.packet_identifier,
+        Packet::Puback(puback) => Some(puback.packet_identifier),
+        Packet::Pubrec(pubrec) => Some(pubrec.packet_identifier),
+        Packet::Pubrel(pubrel) => Some(pubrel.packet_identifier),
+        Packet::Pubcomp(pubcomp) => Some(pubcomp.packet_identifier),
+        Packet::Subscribe(subscribe) => Some(subscribe.packet_identifier),
+        Packet::Suback(suback) => Some(suback.packet_identifier),
+        Packet::Unsubscribe(unsubscribe) => Some(unsubscribe.packet_identifier),
+        Packet::Unsuback(unsuback) => Some(unsuback.packet_identifier),
+        Packet::Auth(_)
+        | Packet::Connack(_)
+        | Packet::Connect(_)
+        | Packet::Disconnect(_)
+        | Packet::Pingreq(_)
+        | Packet::Pingresp(_) => None,
+    }
+    .map(PacketIdentifier::from)
+}
+
+async fn disconnect_with_reason(
+    inner: &Arc<Mutex<InnerClient>>,
+    reason_code: mqtt_format::v5::packets::disconnect::DisconnectReasonCode,
+) {
+    let mut inner = inner.lock().await;
+    let Some(conn_state) = inner.connection_state.as_mut() else {
+        return;
+    };
+
+    let disconnect = mqtt_format::v5::packets::MqttPacket::Disconnect(
+        mqtt_format::v5::packets::disconnect::MDisconnect {
+            reason_code,
+            properties: mqtt_format::v5::packets::disconnect::DisconnectProperties::new(),
+        },
+    );
+    if let Err(error) = conn_state.conn_write.send(disconnect).await {
+        tracing::warn!(%error, "Could not send DISCONNECT");
+        return;
+    }
+    if let Err(error) = conn_state.conn_write.close().await {
+        tracing::warn!(%error, "Could not close the transport");
+    }
+}
+
+/// Whether a packet carries a reason string or user properties where the CONNECT can forbid them
+///
+/// MQTT-3.1.2-29
+fn carries_problem_information(packet: &mqtt_format::v5::packets::MqttPacket<'_>) -> bool {
+    use mqtt_format::v5::packets::MqttPacket as Packet;
+
+    match packet {
+        Packet::Auth(auth) => {
+            auth.properties.reason_string.is_some() || auth.properties.user_properties.is_some()
+        }
+        Packet::Puback(puback) => {
+            puback.properties.reason_string.is_some() || puback.properties.user_properties.is_some()
+        }
+        Packet::Pubrec(pubrec) => {
+            pubrec.properties.reason_string.is_some() || pubrec.properties.user_properties.is_some()
+        }
+        Packet::Pubrel(pubrel) => {
+            pubrel.properties.reason_string.is_some() || pubrel.properties.user_properties.is_some()
+        }
+        Packet::Pubcomp(pubcomp) => {
+            pubcomp.properties.reason_string.is_some()
+                || pubcomp.properties.user_properties.is_some()
+        }
+        Packet::Suback(suback) => {
+            suback.properties.reason_string.is_some() || suback.properties.user_properties.is_some()
+        }
+        Packet::Unsuback(unsuback) => {
+            unsuback.properties.reason_string.is_some()
+                || unsuback.properties.user_properties.is_some()
+        }
+        Packet::Connack(_)
+        | Packet::Connect(_)
+        | Packet::Disconnect(_)
+        | Packet::Pingreq(_)
+        | Packet::Pingresp(_)
+        | Packet::Publish(_)
+        | Packet::Subscribe(_)
+        | Packet::Unsubscribe(_) => false,
+    }
+}
+
+async fn requested_problem_information(inner: &Arc<Mutex<InnerClient>>) -> bool {
+    inner
+        .lock()
+        .await
+        .connection_state
+        .as_ref()
+        .map_or(true, |conn_state| conn_state.requested_problem_information)
+}
+
+/// Whether the server used a topic alias the client did not allow in its CONNECT
+///
+/// MQTT-3.3.2-9
+async fn exceeds_topic_alias_maximum(
+    publish: &mqtt_format::v5::packets::publish::MPublish<'_>,
+    inner: &Arc<Mutex<InnerClient>>,
+) -> bool {
+    let Some(topic_alias) = publish.properties.topic_alias() else {
+        return false;
+    };
+
+    let maximum = inner
+        .lock()
+        .await
+        .connection_state
+        .as_ref()
+        .map_or(0, |conn_state| conn_state.advertised_topic_alias_maximum);
+    topic_alias.0.get() > maximum
+}
+
+async fn handle_auth(
+    auth: &mqtt_format::v5::packets::auth::MAuth<'_>,
+    inner: &Arc<Mutex<InnerClient>>,
+) -> Result<(), ()> {
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+
+    match auth.reason {
+        mqtt_format::v5::packets::auth::AuthReasonCode::Success => {
+            if let Some(cb) = inner.outstanding_callbacks.take_reauthentication() {
+                if cb.on_complete.send(Ok(())).is_err() {
+                    tracing::debug!("Re-authentication completion handler was dropped before receiving response")
+                }
+            } else {
+                tracing::warn!(
+                    "Received an unwarranted successful AUTH from the server, continuing"
+                )
+            }
+        }
+        mqtt_format::v5::packets::auth::AuthReasonCode::ContinueAuthentication => {
+            let Some(reauth) = inner.outstanding_callbacks.reauthentication() else {
+                tracing::warn!("Received an AUTH continuation without an ongoing re-authentication, continuing");
+                return Ok(());
+            };
+
+            let Some(on_auth_continue) = inner.default_handlers.on_auth_continue.as_ref() else {
+                tracing::error!("No handler configured to continue the authentication exchange");
+                if let Some(cb) = inner.outstanding_callbacks.take_reauthentication() {
+                    let _ = cb
+                        .on_complete
+                        .send(Err(ReauthenticationError::NoAuthHandler));
+                }
+                return Ok(());
+            };
+
+            let Some(conn_state) = inner.connection_state.as_mut() else {
+                tracing::error!("No connection state found");
+                return Err(());
+            };
+
+            let method = reauth.method.as_ref();
+            let data = on_auth_continue(
+                method,
+                auth.properties.authentication_data().map(|data| data.0),
+            );
+
+            let mut properties = crate::packets::auth::AuthProperties::new();
+            properties
+                .with_authentication_method(method.to_string())
+                .with_authentication_data(data);
+
+            let packet =
+                mqtt_format::v5::packets::MqttPacket::Auth(mqtt_format::v5::packets::auth::MAuth {
+                    reason: mqtt_format::v5::packets::auth::AuthReasonCode::ContinueAuthentication,
+                    properties: properties.as_ref(),
+                });
+
+            conn_state.conn_write.send(packet).await.map_err(drop)?;
+        }
+        mqtt_format::v5::packets::auth::AuthReasonCode::ReAuthenticate => {
+            tracing::warn!("Received a re-authentication request from the server, which only clients may send. Ignoring and continuing...");
+        }
     }
 
     Ok(())
@@ -104,6 +393,7 @@ async fn handle_pingresp(
 ) -> Result<(), ()> {
     let mut inner = inner.lock().await;
     let inner = &mut *inner;
+    inner.emit_event(ClientEvent::PingAcked);
 
     if let Some(cb) = inner.outstanding_callbacks.take_ping_req() {
         if cb.send(()).is_err() {
@@ -122,39 +412,180 @@ async fn handle_pingreq(_pingreq: &mqtt_format::v5::packets::pingreq::MPingreq)
     Ok(())
 }
 
+async fn handle_publish(packet: &MqttPacket, inner: &Arc<Mutex<InnerClient>>) -> Result<(), ()> {
+    let publish = crate::packets::publish::Publish::try_from(packet.clone())?;
+    let mpublish = publish.get();
+
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    inner.emit_event(ClientEvent::MessageReceived);
+    let Some(ref mut session_state) = inner.session_state else {
+        tracing::error!("No session state found");
+        return Err(());
+    };
+    let Some(ref mut conn_state) = inner.connection_state else {
+        tracing::error!("No connection state found");
+        return Err(());
+    };
+
+    let packet_identifier = mpublish.packet_identifier;
+    let qos = mpublish.quality_of_service;
+
+    if qos == mqtt_format::v5::qos::QualityOfService::ExactlyOnce {
+        let Some(pident) = packet_identifier else {
+            unreachable!("A QoS 2 publish always carries a packet identifier")
+        };
+
+        if session_state
+            .awaiting_pubrel
+            .contains(&PacketIdentifier::from(pident))
+        {
+            tracing::debug!("Received a retransmitted QoS 2 publish, not delivering it again");
+            let pubrec = mqtt_format::v5::packets::MqttPacket::Pubrec(
+                mqtt_format::v5::packets::pubrec::MPubrec {
+                    packet_identifier: pident,
+                    reason: mqtt_format::v5::packets::pubrec::PubrecReasonCode::Success,
+                    properties: mqtt_format::v5::packets::pubrec::PubrecProperties::new(),
+                },
+            );
+            return conn_state.conn_write.send(pubrec).await.map_err(drop);
+        }
+    }
+
+    let dropped = if mpublish.properties.topic_alias().is_some() {
+        // Topic aliases are not supported yet, so we cannot know what topic this publish is for
+        Some(DroppedMessageReason::ProtocolError)
+    } else {
+        match inner.incoming_publishes.as_mut() {
+            None => Some(DroppedMessageReason::NoReceiver),
+            Some(sender) => match sender.try_send(publish.clone()) {
+                Ok(()) => None,
+                Err(e) if e.is_full() => Some(DroppedMessageReason::ChannelFull),
+                Err(_) => Some(DroppedMessageReason::ReceiverDropped),
+            },
+        }
+    };
+
+    if let Some(reason) = dropped {
+        tracing::warn!(?reason, "Dropping received publish");
+        tracing::trace!("Calling on_dropped_message handler");
+        (inner.default_handlers.on_dropped_message)(packet.clone(), reason);
+    }
+
+    match qos {
+        mqtt_format::v5::qos::QualityOfService::AtMostOnce => {}
+        mqtt_format::v5::qos::QualityOfService::AtLeastOnce => {
+            let Some(packet_identifier) = packet_identifier else {
+                unreachable!("A QoS 1 publish always carries a packet identifier")
+            };
+            let reason = if dropped.is_some() {
+                mqtt_format::v5::packets::puback::PubackReasonCode::UnspecifiedError
+            } else {
+                mqtt_format::v5::packets::puback::PubackReasonCode::Success
+            };
+            let puback = mqtt_format::v5::packets::MqttPacket::Puback(
+                mqtt_format::v5::packets::puback::MPuback {
+                    packet_identifier,
+                    reason,
+                    properties: mqtt_format::v5::packets::puback::PubackProperties::new(),
+                },
+            );
+            conn_state.conn_write.send(puback).await.map_err(drop)?;
+        }
+        mqtt_format::v5::qos::QualityOfService::ExactlyOnce => {
+            let Some(packet_identifier) = packet_identifier else {
+                unreachable!("A QoS 2 publish always carries a packet identifier")
+            };
+            let reason = if dropped.is_some() {
+                mqtt_format::v5::packets::pubrec::PubrecReasonCode::UnspecifiedError
+            } else {
+                // A successful PUBREC means the server will follow up with a PUBREL
+                session_state
+                    .awaiting_pubrel
+                    .insert(PacketIdentifier::from(packet_identifier));
+                mqtt_format::v5::packets::pubrec::PubrecReasonCode::Success
+            };
+            let pubrec = mqtt_format::v5::packets::MqttPacket::Pubrec(
+                mqtt_format::v5::packets::pubrec::MPubrec {
+                    packet_identifier,
+                    reason,
+                    properties: mqtt_format::v5::packets::pubrec::PubrecProperties::new(),
+                },
+            );
+            conn_state.conn_write.send(pubrec).await.map_err(drop)?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_pubrel(
+    pubrel: &mqtt_format::v5::packets::pubrel::MPubrel<'_>,
+    inner: &Arc<Mutex<InnerClient>>,
+) -> Result<(), ()> {
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let Some(ref mut session_state) = inner.session_state else {
+        tracing::error!("No session state found");
+        return Err(());
+    };
+    let Some(ref mut conn_state) = inner.connection_state else {
+        tracing::error!("No connection state found");
+        return Err(());
+    };
+
+    let reason = if session_state
+        .awaiting_pubrel
+        .remove(&PacketIdentifier::from(pubrel.packet_identifier))
+    {
+        mqtt_format::v5::packets::pubcomp::PubcompReasonCode::Success
+    } else {
+        tracing::warn!("Received a PUBREL for an unknown packet identifier");
+        mqtt_format::v5::packets::pubcomp::PubcompReasonCode::PacketIdentifierNotFound
+    };
+
+    let pubcomp = mqtt_format::v5::packets::MqttPacket::Pubcomp(
+        mqtt_format::v5::packets::pubcomp::MPubcomp {
+            packet_identifier: pubrel.packet_identifier,
+            reason,
+            properties: mqtt_format::v5::packets::pubcomp::PubcompProperties::new(),
+        },
+    );
+    conn_state.conn_write.send(pubcomp).await.map_err(drop)
+}
+
 async fn handle_pubcomp(
     pubcomp: &mqtt_format::v5::packets::pubcomp::MPubcomp<'_>,
     inner: &Arc<Mutex<InnerClient>>,
     packet: &MqttPacket,
 ) -> Result<(), ()> {
-    match pubcomp.reason {
-        mqtt_format::v5::packets::pubcomp::PubcompReasonCode::Success => {
-            let mut inner = inner.lock().await;
-            let inner = &mut *inner;
-            let Some(ref mut session_state) = inner.session_state else {
-                tracing::error!("No session state found");
-                todo!()
-            };
-            let pident = PacketIdentifier::from(pubcomp.packet_identifier);
-            tracing::Span::current().record("packet_identifier", tracing::field::display(pident));
-
-            if session_state
-                .outstanding_packets
-                .exists_outstanding_packet(pident)
-            {
-                session_state.outstanding_packets.remove_by_id(pident);
-                tracing::trace!("Removed packet id from outstanding packets");
-
-                if let Some(callback) = inner.outstanding_callbacks.take_qos2_complete(pident) {
-                    if callback.on_complete.send(packet.clone()).is_err() {
-                        tracing::trace!("Could not send ack, receiver was dropped.")
-                    }
-                } else {
-                    todo!("Invariant broken: Received on_complete for unknown packet")
-                }
+    if pubcomp.reason != mqtt_format::v5::packets::pubcomp::PubcompReasonCode::Success {
+        // The server lost the PUBREL state, the flow still ends here
+        tracing::warn!(reason = ?pubcomp.reason, "Server completed the publish with an error");
+    }
+
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let Some(ref mut session_state) = inner.session_state else {
+        tracing::error!("No session state found");
+        return Err(());
+    };
+    let pident = PacketIdentifier::from(pubcomp.packet_identifier);
+
+    if session_state
+        .outstanding_packets
+        .exists_outstanding_packet(pident)
+    {
+        session_state.outstanding_packets.remove_by_id(pident);
+        tracing::trace!("Removed packet id from outstanding packets");
+
+        if let Some(callback) = inner.outstanding_callbacks.take_qos2_complete(pident) {
+            if callback.on_complete.send(packet.clone()).is_err() {
+                tracing::trace!("Could not send ack, receiver was dropped.")
             }
+        } else {
+            tracing::debug!("Nobody is waiting for the PUBCOMP anymore");
         }
-        _ => todo!("Handle errors"),
     }
 
     Ok(())
@@ -168,40 +599,114 @@ async fn handle_puback(
     (inner.lock().await.default_handlers.on_qos1_acknowledge)(puback.clone());
     let mpuback = puback.get();
 
-    match mpuback.reason {
+    if !matches!(
+        mpuback.reason,
         mqtt_format::v5::packets::puback::PubackReasonCode::Success
-        | mqtt_format::v5::packets::puback::PubackReasonCode::NoMatchingSubscribers => {
-            let mut inner = inner.lock().await;
-            let inner = &mut *inner;
-            let Some(ref mut session_state) = inner.session_state else {
-                tracing::error!("No session state found");
-                todo!()
-            };
+            | mqtt_format::v5::packets::puback::PubackReasonCode::NoMatchingSubscribers
+    ) {
+        // A failure reason still ends the QoS 1 flow, the reason is part of the forwarded PUBACK
+        tracing::warn!(reason = ?mpuback.reason, "Server did not accept the publish");
+    }
+
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let Some(ref mut session_state) = inner.session_state else {
+        tracing::error!("No session state found");
+        return Err(());
+    };
 
-            let pident = PacketIdentifier::from(mpuback.packet_identifier);
-            tracing::Span::current().record("packet_identifier", tracing::field::display(pident));
+    let pident = PacketIdentifier::from(mpuback.packet_identifier);
 
-            if session_state
-                .outstanding_packets
-                .exists_outstanding_packet(pident)
-            {
-                session_state.outstanding_packets.remove_by_id(pident);
-                tracing::trace!("Removed packet id from outstanding packets");
+    if session_state
+        .outstanding_packets
+        .exists_outstanding_packet(pident)
+    {
+        session_state.outstanding_packets.remove_by_id(pident);
+        tracing::trace!("Removed packet id from outstanding packets");
 
-                if let Some(callback) = inner.outstanding_callbacks.take_qos1(pident) {
-                    if callback.on_acknowledge.send(puback.clone()).is_err() {
-                        tracing::trace!("Could not send ack, receiver was dropped.")
-                    }
-                }
-            } else {
-                tracing::error!("Packet id does not exist in outstanding packets");
-                todo!()
+        if let Some(callback) = inner.outstanding_callbacks.take_qos1(pident) {
+            if callback.on_acknowledge.send(puback.clone()).is_err() {
+                tracing::trace!("Could not send ack, receiver was dropped.")
             }
+        }
+    } else {
+        // Resending a publish can cause the server to acknowledge it twice
+        tracing::warn!("Packet id does not exist in outstanding packets");
+    }
+
+    // TODO: Forward mpuback.properties etc to the user
+
+    Ok(())
+}
+
+async fn handle_suback(
+    suback: &mqtt_format::v5::packets::suback::MSuback<'_>,
+    inner: &Arc<Mutex<InnerClient>>,
+) -> Result<(), ()> {
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let pident = PacketIdentifier::from(suback.packet_identifier);
+
+    let Some(callback) = inner.outstanding_callbacks.take_subscribe(pident) else {
+        tracing::warn!("Received SUBACK for an unknown packet identifier");
+        return Ok(());
+    };
 
-            // TODO: Forward mpuback.properties etc to the user
+    let granted = callback
+        .topic_filters
+        .into_iter()
+        .zip(suback.reasons)
+        .filter_map(|(topic_filter, reason)| Some((topic_filter, granted_qos(*reason)?)))
+        .collect::<Vec<_>>();
+
+    if let Some(session_state) = inner.session_state.as_mut() {
+        session_state.subscriptions.extend(
+            granted
+                .iter()
+                .map(|(topic_filter, qos)| (topic_filter.clone(), *qos)),
+        );
+    }
+
+    let subscribed = Subscribed {
+        reasons: suback.reasons.to_vec(),
+        granted,
+    };
+    if callback.on_suback.send(subscribed).is_err() {
+        tracing::trace!("Could not send SUBACK, receiver was dropped.")
+    }
+
+    Ok(())
+}
+
+async fn handle_unsuback(
+    unsuback: &mqtt_format::v5::packets::unsuback::MUnsuback<'_>,
+    inner: &Arc<Mutex<InnerClient>>,
+) -> Result<(), ()> {
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let pident = PacketIdentifier::from(unsuback.packet_identifier);
+
+    let Some(callback) = inner.outstanding_callbacks.take_unsubscribe(pident) else {
+        tracing::warn!("Received UNSUBACK for an unknown packet identifier");
+        return Ok(());
+    };
+
+    if let Some(session_state) = inner.session_state.as_mut() {
+        for (topic_filter, reason) in callback.topic_filters.iter().zip(unsuback.reasons) {
+            if matches!(
+                reason,
+                UnsubackReasonCode::Success | UnsubackReasonCode::NoSubscriptionExisted
+            ) {
+                session_state.subscriptions.remove(topic_filter);
+            }
         }
+    }
 
-        _ => todo!("Handle errors"),
+    let unsubscribed = Unsubscribed {
+        reasons: unsuback.reasons.to_vec(),
+    };
+    if callback.on_unsuback.send(unsubscribed).is_err() {
+        tracing::trace!("Could not send UNSUBACK, receiver was dropped.")
     }
 
     Ok(())
@@ -212,59 +717,481 @@ async fn handle_pubrec(
     inner: &Arc<Mutex<InnerClient>>,
     packet: &MqttPacket,
 ) -> Result<(), ()> {
-    match pubrec.reason {
-        mqtt_format::v5::packets::pubrec::PubrecReasonCode::Success => {
-            let mut inner = inner.lock().await;
-            let inner = &mut *inner;
-            let Some(ref mut session_state) = inner.session_state else {
-                tracing::error!("No session state found");
-                todo!()
-            };
-            let Some(ref mut conn_state) = inner.connection_state else {
-                tracing::error!("No session state found");
-                todo!()
-            };
-            let pident = PacketIdentifier::from(pubrec.packet_identifier);
-            tracing::Span::current().record("packet_identifier", tracing::field::display(pident));
-
-            if session_state
-                .outstanding_packets
-                .exists_outstanding_packet(pident)
-            {
-                let pubrel = mqtt_format::v5::packets::MqttPacket::Pubrel(
-                    mqtt_format::v5::packets::pubrel::MPubrel {
-                        packet_identifier: pubrec.packet_identifier,
-                        reason: mqtt_format::v5::packets::pubrel::PubrelReasonCode::Success,
-                        properties: mqtt_format::v5::packets::pubrel::PubrelProperties::new(),
-                    },
-                );
-
-                let mut bytes = tokio_util::bytes::BytesMut::new();
-                bytes.reserve(pubrel.binary_size() as usize);
-                pubrel.write(&mut MqttWriter(&mut bytes)).map_err(drop)?;
-                let pubrel_packet = MqttPacket {
-                    packet: Yoke::try_attach_to_cart(StableBytes(bytes.freeze()), |bytes| {
-                        mqtt_format::v5::packets::MqttPacket::parse_complete(bytes)
-                    })
-                    .unwrap(),
-                };
-                session_state
-                    .outstanding_packets
-                    .update_by_id(pident, pubrel_packet);
-                tracing::trace!("Update packet from outstanding packets");
-                conn_state.conn_write.send(pubrel).await.map_err(drop)?;
-
-                if let Some(callback) = inner.outstanding_callbacks.take_qos2_receive(pident) {
-                    if callback.on_receive.send(packet.clone()).is_err() {
-                        tracing::trace!("Could not send ack, receiver was dropped.")
-                    }
-                } else {
-                    todo!("Invariant broken: Receive PubRec for unawaited {pident}")
-                }
+    if !pubrec_accepted(pubrec.reason) {
+        // A failed PUBREC ends the flow without a PUBREL, the identifier can be reused right away
+        tracing::warn!(reason = ?pubrec.reason, "Server did not accept the QoS 2 publish");
+
+        let mut inner = inner.lock().await;
+        let inner = &mut *inner;
+        let Some(ref mut session_state) = inner.session_state else {
+            tracing::error!("No session state found");
+            return Err(());
+        };
+        let pident = PacketIdentifier::from(pubrec.packet_identifier);
+
+        session_state.outstanding_packets.remove_by_id(pident);
+        inner.outstanding_callbacks.take_qos2_complete(pident);
+        if let Some(callback) = inner.outstanding_callbacks.take_qos2_receive(pident) {
+            if callback.on_receive.send(packet.clone()).is_err() {
+                tracing::trace!("Could not send the rejection, receiver was dropped.")
+            }
+        }
+
+        return Ok(());
+    }
+
+    let mut inner = inner.lock().await;
+    let inner = &mut *inner;
+    let Some(ref mut session_state) = inner.session_state else {
+        tracing::error!("No session state found");
+        return Err(());
+    };
+    let Some(ref mut conn_state) = inner.connection_state else {
+        tracing::error!("No connection state found");
+        return Err(());
+    };
+    let pident = PacketIdentifier::from(pubrec.packet_identifier);
+
+    if session_state
+        .outstanding_packets
+        .exists_outstanding_packet(pident)
+    {
+        let pubrel = mqtt_format::v5::packets::MqttPacket::Pubrel(
+            mqtt_format::v5::packets::pubrel::MPubrel {
+                packet_identifier: pubrec.packet_identifier,
+                reason: mqtt_format::v5::packets::pubrel::PubrelReasonCode::Success,
+                properties: mqtt_format::v5::packets::pubrel::PubrelProperties::new(),
+            },
+        );
+
+        let mut bytes = tokio_util::bytes::BytesMut::new();
+        bytes.reserve(pubrel.binary_size() as usize);
+        pubrel.write(&mut MqttWriter(&mut bytes)).map_err(drop)?;
+        let pubrel_packet = MqttPacket {
+            packet: Yoke::try_attach_to_cart(StableBytes(bytes.freeze()), |bytes| {
+                mqtt_format::v5::packets::MqttPacket::parse_complete(bytes)
+            })
+            .unwrap(),
+        };
+        session_state
+            .outstanding_packets
+            .update_by_id(pident, pubrel_packet);
+        tracing::trace!("Update packet from outstanding packets");
+        conn_state.conn_write.send(pubrel).await.map_err(drop)?;
+
+        if let Some(callback) = inner.outstanding_callbacks.take_qos2_receive(pident) {
+            if callback.on_receive.send(packet.clone()).is_err() {
+                tracing::trace!("Could not send ack, receiver was dropped.")
             }
+        } else {
+            tracing::debug!("Nobody is waiting for the PUBREC anymore");
         }
-        _ => todo!("Handle errors"),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use mqtt_format::v5::packets::connack::ConnackProperties;
+    use mqtt_format::v5::packets::connack::ConnackReasonCode;
+    use mqtt_format::v5::packets::connack::MConnack;
+    use mqtt_format::v5::packets::disconnect::DisconnectReasonCode;
+    use mqtt_format::v5::packets::puback::MPuback;
+    use mqtt_format::v5::packets::puback::PubackProperties;
+    use mqtt_format::v5::packets::puback::PubackReasonCode;
+    use mqtt_format::v5::packets::pubcomp::MPubcomp;
+    use mqtt_format::v5::packets::pubcomp::PubcompProperties;
+    use mqtt_format::v5::packets::pubcomp::PubcompReasonCode;
+    use mqtt_format::v5::packets::publish::MPublish;
+    use mqtt_format::v5::packets::publish::PublishProperties;
+    use mqtt_format::v5::packets::pubrec::MPubrec;
+    use mqtt_format::v5::packets::pubrec::PubrecProperties;
+    use mqtt_format::v5::packets::pubrec::PubrecReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::QualityOfService as FormatQualityOfService;
+    use mqtt_format::v5::variable_header::PacketIdentifier as FormatPacketIdentifier;
+    use mqtt_format::v5::variable_header::ReasonString;
+    use mqtt_format::v5::variable_header::TopicAlias;
+    use tracing::instrument::WithSubscriber;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::client::connect::ConnectionStatus;
+    use crate::client::connect::DisconnectInfo;
+    use crate::client::send::Publish;
+    use crate::client::send::PublishFailed;
+    use crate::client::MqttClient;
+    use crate::qos::QualityOfService;
+
+    type SpanFields = HashMap<&'static str, String>;
+
+    struct FieldVisitor<'a>(&'a mut SpanFields);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    /// Collects the recorded fields of all closed "Processing packet" spans
+    #[derive(Clone, Default)]
+    struct ProcessSpanLayer(Arc<Mutex<Vec<SpanFields>>>);
+
+    impl<S> tracing_subscriber::Layer<S> for ProcessSpanLayer
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() == "Processing packet" {
+                let mut fields = SpanFields::new();
+                attrs.record(&mut FieldVisitor(&mut fields));
+                ctx.span(id).unwrap().extensions_mut().insert(fields);
+            }
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<SpanFields>() {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+
+        fn on_close(&self, id: tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions_mut().remove::<SpanFields>();
+            if let Some(fields) = fields {
+                self.0.lock().unwrap().push(fields);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn process_span_records_kind_and_identifier() {
+        let layer = ProcessSpanLayer::default();
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let background = tokio::spawn(
+            connected
+                .unwrap()
+                .background_task
+                .with_subscriber(subscriber),
+        );
+
+        let published = client
+            .publish(Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::ExactlyOnce,
+                retain: false,
+                payload: vec![123].try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH packet, got: {packet:?}");
+        };
+        let packet_identifier = publish.packet_identifier.unwrap();
+
+        server
+            .send(FormatMqttPacket::Pubrec(MPubrec {
+                packet_identifier,
+                reason: PubrecReasonCode::Success,
+                properties: PubrecProperties::new(),
+            }))
+            .await;
+        let pubrel = server.receive().await;
+        assert!(matches!(pubrel.get(), FormatMqttPacket::Pubrel(_)));
+        server
+            .send(FormatMqttPacket::Pubcomp(MPubcomp {
+                packet_identifier,
+                reason: PubcompReasonCode::Success,
+                properties: PubcompProperties::new(),
+            }))
+            .await;
+        published.acknowledged().await.unwrap();
+
+        let ping = client.ping().await.unwrap();
+        assert!(matches!(
+            server.receive().await.get(),
+            FormatMqttPacket::Pingreq(_)
+        ));
+        server
+            .send(FormatMqttPacket::Pingresp(
+                mqtt_format::v5::packets::pingresp::MPingresp,
+            ))
+            .await;
+        ping.response().await;
+
+        drop(server);
+        background.await.unwrap();
+
+        let spans = layer.0.lock().unwrap();
+        let recorded = spans
+            .iter()
+            .map(|fields| {
+                (
+                    fields["packet_kind"].as_str(),
+                    fields.get("packet_identifier").map(String::as_str),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let packet_identifier = packet_identifier.0.to_string();
+        assert_eq!(
+            recorded,
+            [
+                ("Pubrec", Some(packet_identifier.as_str())),
+                ("Pubcomp", Some(packet_identifier.as_str())),
+                ("Pingresp", None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn second_connack_disconnects_with_protocol_error() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let background_task = tokio::spawn(connected.unwrap().background_task);
+
+        server
+            .send(FormatMqttPacket::Connack(MConnack {
+                session_present: false,
+                reason_code: ConnackReasonCode::Success,
+                properties: ConnackProperties::new(),
+            }))
+            .await;
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Disconnect(disconnect) = packet.get() else {
+            panic!("Expected a DISCONNECT, got: {packet:?}");
+        };
+        assert_eq!(disconnect.reason_code, DisconnectReasonCode::ProtocolError);
+
+        assert!(matches!(
+            background_task.await.unwrap(),
+            DisconnectInfo::ProtocolError
+        ));
+        assert_eq!(
+            client.connection_state().await,
+            ConnectionStatus::Disconnected
+        );
+    }
+
+    #[tokio::test]
+    async fn topic_alias_above_advertised_maximum_disconnects() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.properties_mut().with_topic_alias_maximum(2);
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        let background_task = tokio::spawn(connected.unwrap().background_task);
+
+        let publish = |packet_identifier: u16, topic_alias: u16| {
+            FormatMqttPacket::Publish(MPublish {
+                duplicate: false,
+                quality_of_service: FormatQualityOfService::AtLeastOnce,
+                retain: false,
+                topic_name: "",
+                packet_identifier: Some(FormatPacketIdentifier(
+                    packet_identifier.try_into().unwrap(),
+                )),
+                properties: PublishProperties {
+                    topic_alias: Some(TopicAlias(topic_alias.try_into().unwrap())),
+                    ..PublishProperties::new()
+                },
+                payload: b"hello",
+            })
+        };
+
+        // An alias within the maximum keeps the connection, even though it cannot be resolved
+        server.send(publish(1, 2)).await;
+        let packet = server.receive().await;
+        let FormatMqttPacket::Puback(puback) = packet.get() else {
+            panic!("Expected a PUBACK, got: {packet:?}");
+        };
+        assert_eq!(puback.reason, PubackReasonCode::UnspecifiedError);
+
+        server.send(publish(2, 3)).await;
+        let packet = server.receive().await;
+        let FormatMqttPacket::Disconnect(disconnect) = packet.get() else {
+            panic!("Expected a DISCONNECT, got: {packet:?}");
+        };
+        assert_eq!(
+            disconnect.reason_code,
+            DisconnectReasonCode::TopicAliasInvalid
+        );
+
+        assert!(matches!(
+            background_task.await.unwrap(),
+            DisconnectInfo::ProtocolError
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_puback_releases_the_packet_identifier() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        let background_task = tokio::spawn(connected.unwrap().background_task);
+
+        let published = client
+            .publish(Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtLeastOnce,
+                retain: false,
+                payload: vec![123].try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH packet, got: {packet:?}");
+        };
+        server
+            .send(FormatMqttPacket::Puback(MPuback {
+                packet_identifier: publish.packet_identifier.unwrap(),
+                reason: PubackReasonCode::NotAuthorized,
+                properties: PubackProperties::new(),
+            }))
+            .await;
+
+        published.acknowledged().await.unwrap();
+        assert!(client.outstanding_packet_ids().await.is_empty());
+
+        // The connection survives the failed publish
+        let ping = client.ping().await.unwrap();
+        assert!(matches!(
+            server.receive().await.get(),
+            FormatMqttPacket::Pingreq(_)
+        ));
+        server
+            .send(FormatMqttPacket::Pingresp(
+                mqtt_format::v5::packets::pingresp::MPingresp,
+            ))
+            .await;
+        ping.response().await;
+        assert!(!background_task.is_finished());
+    }
+
+    #[tokio::test]
+    async fn failed_pubrec_aborts_the_qos2_flow() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let published = client
+            .publish(Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::ExactlyOnce,
+                retain: false,
+                payload: vec![123].try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH packet, got: {packet:?}");
+        };
+        server
+            .send(FormatMqttPacket::Pubrec(MPubrec {
+                packet_identifier: publish.packet_identifier.unwrap(),
+                reason: PubrecReasonCode::NotAuthorized,
+                properties: PubrecProperties::new(),
+            }))
+            .await;
+
+        assert!(matches!(
+            published.acknowledged().await,
+            Err(PublishFailed::Rejected(PubrecReasonCode::NotAuthorized))
+        ));
+        assert!(client.outstanding_packet_ids().await.is_empty());
+
+        // No PUBREL is sent, the next packet is the PINGREQ
+        let _ping = client.ping().await.unwrap();
+        let packet = server.receive().await;
+        assert!(
+            matches!(packet.get(), FormatMqttPacket::Pingreq(_)),
+            "Expected a PINGREQ, got: {packet:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn unrequested_reason_string_disconnects() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_request_problem_information(false);
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        let background_task = tokio::spawn(connected.unwrap().background_task);
+
+        let _published = client
+            .publish(Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtLeastOnce,
+                retain: false,
+                payload: vec![123].try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH packet, got: {packet:?}");
+        };
+
+        server
+            .send(FormatMqttPacket::Puback(MPuback {
+                packet_identifier: publish.packet_identifier.unwrap(),
+                reason: PubackReasonCode::NotAuthorized,
+                properties: PubackProperties {
+                    reason_string: Some(ReasonString("not for you")),
+                    ..PubackProperties::new()
+                },
+            }))
+            .await;
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Disconnect(disconnect) = packet.get() else {
+            panic!("Expected a DISCONNECT, got: {packet:?}");
+        };
+        assert_eq!(disconnect.reason_code, DisconnectReasonCode::ProtocolError);
+        assert!(matches!(
+            background_task.await.unwrap(),
+            DisconnectInfo::ProtocolError
+        ));
+    }
+}
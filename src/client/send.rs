@@ -6,21 +6,48 @@
 
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use mqtt_format::v5::integers::VARIABLE_INTEGER_MAX;
 use mqtt_format::v5::packets::publish::MPublish;
 use tracing::Instrument;
 
+use super::auth::ReauthenticationCallback;
+use super::events::ClientEvent;
 use super::state::OutstandingPackets;
+use super::subscribe::SubscribeCallback;
+use super::unsubscribe::UnsubscribeCallback;
 use super::MqttClient;
 use crate::packet_identifier::PacketIdentifier;
+use crate::packet_identifier::PacketIdentifierAllocator;
+use crate::packets::publish::PublishProperties;
 use crate::packets::MqttPacket;
 use crate::payload::MqttPayload;
+use crate::properties::UserProperty;
 use crate::qos::QualityOfService;
+use crate::string::MqttString;
+use crate::topic::MqttTopic;
 
 impl MqttClient {
+    pub async fn publish(&self, publish: Publish) -> Result<Published, ()> {
+        self.publish_with_properties(publish, PublishProperties::new())
+            .await
+    }
+
+    /// Start building a publish to `topic`, which allows setting the properties of the publish
+    pub fn publish_builder(&self, topic: MqttTopic) -> PublishBuilder<'_> {
+        PublishBuilder {
+            client: self,
+            topic,
+            qos: QualityOfService::AtMostOnce,
+            retain: false,
+            payload: None,
+            properties: PublishProperties::new(),
+        }
+    }
+
     #[tracing::instrument(skip_all, fields(payload_length = payload.as_ref().len()))]
-    pub async fn publish(
+    async fn publish_with_properties(
         &self,
         Publish {
             topic,
@@ -29,6 +56,7 @@ pub async fn publish(
             payload,
             on_packet_recv: _,
         }: Publish,
+        mut properties: PublishProperties,
     ) -> Result<Published, ()> {
         let mut inner = self.inner.lock().await;
         let inner = &mut *inner;
@@ -43,15 +71,25 @@ pub async fn publish(
             return Err(());
         };
 
-        if conn_state.retain_available.unwrap_or(true) && retain {
+        if !conn_state.retain_available.unwrap_or(true) && retain {
             tracing::warn!("Retain not available, but requested");
             return Err(());
         }
 
+        if qos > conn_state.effective_maximum_qos() {
+            tracing::warn!(
+                ?qos,
+                maximum_qos = ?conn_state.effective_maximum_qos(),
+                "Requested QoS is higher than the server supports"
+            );
+            return Err(());
+        }
+
         let packet_identifier = if qos > QualityOfService::AtMostOnce {
             get_next_packet_ident(
-                &mut conn_state.next_packet_identifier,
+                conn_state.packet_identifiers.as_mut(),
                 &sess_state.outstanding_packets,
+                &inner.outstanding_callbacks,
             )
             .map(Some)
             .map_err(|_| ())? // TODO
@@ -60,6 +98,15 @@ pub async fn publish(
         };
         tracing::debug!(?packet_identifier, "Packet identifier computed");
 
+        if properties.content_type.is_none() {
+            if let Some(content_type) = payload.content_type() {
+                properties.with_content_type(content_type.to_string());
+            }
+        }
+        if properties.payload_format_indicator.is_none() && payload.is_utf8() {
+            properties.with_payload_format_indicator(1);
+        }
+
         let publish = MPublish {
             duplicate: false,
             quality_of_service: qos.into(),
@@ -67,30 +114,31 @@ pub async fn publish(
             topic_name: topic.as_ref(),
             packet_identifier: packet_identifier
                 .map(mqtt_format::v5::variable_header::PacketIdentifier::from),
-            properties: mqtt_format::v5::packets::publish::PublishProperties::new(),
+            properties: properties.as_ref(),
             payload: payload.as_ref(),
         };
 
-        let packet = mqtt_format::v5::packets::MqttPacket::Publish(publish);
+        let packet = mqtt_format::v5::packets::MqttPacket::Publish(publish).into_sized();
+        let packet_size = packet.encoded_size();
 
         let maximum_packet_size = conn_state
             .maximum_packet_size
             .unwrap_or(VARIABLE_INTEGER_MAX);
 
-        if packet.binary_size() > maximum_packet_size {
+        if packet_size > maximum_packet_size {
             tracing::error!("Binary size bigger than maximum packet size");
             return Err(());
         }
 
-        tracing::trace!(%maximum_packet_size, packet_size = packet.binary_size(), "Packet size");
+        tracing::trace!(%maximum_packet_size, %packet_size, "Packet size");
 
         let published_recv;
 
         if let Some(pi) = packet_identifier {
             let mut bytes = tokio_util::bytes::BytesMut::new();
-            bytes.reserve(packet.binary_size() as usize);
+            bytes.reserve(packet_size as usize);
             let mut writer = crate::packets::MqttWriter(&mut bytes);
-            packet.write(&mut writer).map_err(drop)?; // TODO
+            packet.packet().write(&mut writer).map_err(drop)?; // TODO
             let mqtt_packet = crate::packets::MqttPacket {
                 packet: yoke::Yoke::try_attach_to_cart(
                     crate::packets::StableBytes(bytes.freeze()),
@@ -99,7 +147,10 @@ pub async fn publish(
                 .unwrap(), // TODO
             };
 
-            sess_state.outstanding_packets.insert(pi, mqtt_packet);
+            if let Err(error) = sess_state.outstanding_packets.insert(pi, mqtt_packet) {
+                tracing::error!(%error, "Could not track the publish");
+                return Err(());
+            }
             match qos {
                 QualityOfService::AtMostOnce => unreachable!(),
                 QualityOfService::AtLeastOnce => {
@@ -128,10 +179,10 @@ pub async fn publish(
         tracing::trace!("Publishing");
         conn_state
             .conn_write
-            .send(packet)
+            .send(packet.into_inner())
             .in_current_span()
             .await
-            .unwrap();
+            .map_err(|error| tracing::error!(%error, "Could not send PUBLISH"))?;
         tracing::trace!("Finished publishing");
 
         Ok(Published {
@@ -151,7 +202,7 @@ pub async fn publish_qos1(
         let _res = self
             .publish(Publish {
                 topic,
-                qos: QualityOfService::AtMostOnce,
+                qos: QualityOfService::AtLeastOnce,
                 retain,
                 payload,
                 on_packet_recv,
@@ -184,28 +235,17 @@ pub async fn publish_qos2(
     }
 }
 
-fn get_next_packet_ident(
-    next_packet_ident: &mut std::num::NonZeroU16,
+pub(super) fn get_next_packet_ident(
+    allocator: &mut dyn PacketIdentifierAllocator,
     outstanding_packets: &OutstandingPackets,
+    callbacks: &Callbacks,
 ) -> Result<PacketIdentifier, PacketIdentifierExhausted> {
-    let start = *next_packet_ident;
-
-    loop {
-        let next = PacketIdentifier::from(*next_packet_ident);
-
-        if !outstanding_packets.exists_outstanding_packet(next) {
-            return Ok(next);
-        }
-
-        match next_packet_ident.checked_add(1) {
-            Some(n) => *next_packet_ident = n,
-            None => *next_packet_ident = std::num::NonZeroU16::MIN,
-        }
-
-        if start == *next_packet_ident {
-            return Err(PacketIdentifierExhausted);
-        }
-    }
+    allocator
+        .allocate(&|ident| {
+            outstanding_packets.exists_outstanding_packet(ident)
+                || callbacks.identifier_in_use(ident)
+        })
+        .ok_or(PacketIdentifierExhausted)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -215,6 +255,9 @@ fn get_next_packet_ident(
 pub(crate) struct ClientHandlers {
     pub(crate) on_packet_recv: OnPacketRecvFn,
     pub(crate) on_qos1_acknowledge: OnQos1AcknowledgeFn,
+    pub(crate) on_auth_continue: Option<OnAuthContinueFn>,
+    pub(crate) on_dropped_message: OnDroppedMessageFn,
+    pub(crate) on_redirect: Option<OnRedirectFn>,
     // on_qos2_receive: Box<dyn Fn(crate::packets::MqttPacket) + Send>,
     // on_qos2_complete: Box<dyn Fn(crate::packets::MqttPacket) + Send>,
 }
@@ -222,12 +265,23 @@ pub(crate) struct ClientHandlers {
 pub type OnPacketRecvFn = Box<dyn Fn(crate::packets::MqttPacket) + Send>;
 pub type OnPacketRefRecvFn = Box<dyn Fn(&crate::packets::MqttPacket) + Send>;
 pub type OnQos1AcknowledgeFn = Box<dyn Fn(crate::packets::Puback) + Send>;
+/// Called with the authentication method and data of a received AUTH packet, returns the
+/// authentication data to send back to the server
+pub type OnAuthContinueFn = Box<dyn Fn(&str, Option<&[u8]>) -> Vec<u8> + Send>;
+pub type OnDroppedMessageFn =
+    Box<dyn Fn(crate::packets::MqttPacket, super::incoming::DroppedMessageReason) + Send>;
+/// Called with the server reference of a DISCONNECT that redirects the client, returns the
+/// connector to reach that server with or `None` to not follow the redirect
+pub type OnRedirectFn = Box<dyn Fn(&str) -> Option<super::connect::MqttClientConnector> + Send>;
 
 impl Default for ClientHandlers {
     fn default() -> Self {
         Self {
             on_packet_recv: Box::new(|_| ()),
             on_qos1_acknowledge: Box::new(|_| ()),
+            on_auth_continue: None,
+            on_dropped_message: Box::new(|_, _| ()),
+            on_redirect: None,
         }
     }
 }
@@ -244,6 +298,9 @@ pub(crate) struct Callbacks {
     qos1: HashMap<PacketIdentifier, Qos1Callbacks>,
     qos2_receive: HashMap<PacketIdentifier, Qos2ReceiveCallback>,
     qos2_complete: HashMap<PacketIdentifier, Qos2CompleteCallback>,
+    subscribe: HashMap<PacketIdentifier, SubscribeCallback>,
+    unsubscribe: HashMap<PacketIdentifier, UnsubscribeCallback>,
+    reauthentication: Option<ReauthenticationCallback>,
 }
 
 impl Callbacks {
@@ -253,6 +310,9 @@ pub(crate) fn new() -> Callbacks {
             qos1: HashMap::default(),
             qos2_receive: HashMap::default(),
             qos2_complete: HashMap::default(),
+            subscribe: HashMap::default(),
+            unsubscribe: HashMap::default(),
+            reauthentication: None,
         }
     }
 
@@ -274,8 +334,63 @@ pub(crate) fn add_qos2(
         self.qos2_complete.insert(id, comp);
     }
 
+    pub(crate) fn set_reauthentication(&mut self, cb: ReauthenticationCallback) {
+        if self.reauthentication.replace(cb).is_some() {
+            tracing::debug!("Replaced an unfinished re-authentication");
+        }
+    }
+
+    pub(crate) fn reauthentication(&mut self) -> Option<&ReauthenticationCallback> {
+        self.reauthentication.as_ref()
+    }
+
+    pub(crate) fn take_reauthentication(&mut self) -> Option<ReauthenticationCallback> {
+        self.reauthentication.take()
+    }
+
+    /// The oldest ping that somebody is still waiting on
+    ///
+    /// Pings given up on, e.g. by a timed out [`MqttClient::is_alive`], are skipped so a late
+    /// PINGRESP cannot get stuck on them.
     pub(crate) fn take_ping_req(&mut self) -> Option<futures::channel::oneshot::Sender<()>> {
-        self.ping_req.pop_front()
+        while let Some(cb) = self.ping_req.pop_front() {
+            if !cb.is_canceled() {
+                return Some(cb);
+            }
+        }
+
+        None
+    }
+
+    /// Drop the callbacks of all unacknowledged publishes, subscribes and unsubscribes, failing
+    /// everyone waiting on them
+    pub(crate) fn abort_outstanding(&mut self) {
+        self.qos1.clear();
+        self.qos2_receive.clear();
+        self.qos2_complete.clear();
+        self.subscribe.clear();
+        self.unsubscribe.clear();
+    }
+
+    pub(crate) fn add_subscribe(&mut self, id: PacketIdentifier, cb: SubscribeCallback) {
+        self.subscribe.insert(id, cb);
+    }
+
+    pub(crate) fn take_subscribe(&mut self, id: PacketIdentifier) -> Option<SubscribeCallback> {
+        self.subscribe.remove(&id)
+    }
+
+    pub(crate) fn add_unsubscribe(&mut self, id: PacketIdentifier, cb: UnsubscribeCallback) {
+        self.unsubscribe.insert(id, cb);
+    }
+
+    /// Whether a SUBSCRIBE or UNSUBSCRIBE with this identifier still awaits its acknowledgement
+    pub(crate) fn identifier_in_use(&self, id: PacketIdentifier) -> bool {
+        self.subscribe.contains_key(&id) || self.unsubscribe.contains_key(&id)
+    }
+
+    pub(crate) fn take_unsubscribe(&mut self, id: PacketIdentifier) -> Option<UnsubscribeCallback> {
+        self.unsubscribe.remove(&id)
     }
 
     pub(crate) fn take_qos1(&mut self, id: PacketIdentifier) -> Option<Qos1Callbacks> {
@@ -316,24 +431,129 @@ pub struct Publish {
     pub on_packet_recv: Option<OnPacketRefRecvFn>,
 }
 
+/// Assembles a publish together with its properties, see [`MqttClient::publish_builder`]
+pub struct PublishBuilder<'c> {
+    client: &'c MqttClient,
+    topic: MqttTopic,
+    qos: QualityOfService,
+    retain: bool,
+    payload: Option<MqttPayload>,
+    properties: PublishProperties,
+}
+
+impl PublishBuilder<'_> {
+    pub fn qos(mut self, qos: QualityOfService) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    pub fn retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    pub fn payload(mut self, payload: MqttPayload) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Overrides the content type derived from the payload
+    pub fn content_type(mut self, content_type: MqttString) -> Self {
+        self.properties
+            .with_content_type(content_type.as_ref().to_string());
+        self
+    }
+
+    pub fn message_expiry_interval(mut self, seconds: u32) -> Self {
+        self.properties.with_message_expiry_interval(seconds);
+        self
+    }
+
+    pub fn response_topic(mut self, topic: MqttTopic) -> Self {
+        self.properties
+            .with_response_topic(topic.as_ref().to_string());
+        self
+    }
+
+    pub fn correlation_data(mut self, data: crate::bytes::MqttBytes) -> Self {
+        self.properties
+            .with_correlation_data(data.as_ref().to_vec());
+        self
+    }
+
+    /// Add a user property, can be called multiple times
+    pub fn user_property(mut self, key: MqttString, value: MqttString) -> Self {
+        self.properties
+            .with_user_properties(UserProperty::new(key, value));
+        self
+    }
+
+    /// Send the publish, without a payload if none was set
+    pub async fn send(self) -> Result<Published, ()> {
+        let payload = match self.payload {
+            Some(payload) => payload,
+            None => MqttPayload::try_from(Vec::new()).map_err(drop)?,
+        };
+
+        self.client
+            .publish_with_properties(
+                Publish {
+                    topic: self.topic,
+                    qos: self.qos,
+                    retain: self.retain,
+                    payload,
+                    on_packet_recv: None,
+                },
+                self.properties,
+            )
+            .await
+    }
+}
+
 pub struct Published {
     recv: PublishedReceiver,
 }
 
 impl Published {
-    pub async fn acknowledged(self) {
+    pub async fn acknowledged(self) -> Result<(), PublishFailed> {
         match self.recv {
             PublishedReceiver::None => (),
             PublishedReceiver::Once(qos1) => {
-                qos1.acknowledged().await;
+                qos1.acknowledged().await?;
             }
             PublishedReceiver::Twice(qos2) => {
-                qos2.received().await.completed().await;
+                qos2.received().await?.completed().await?;
             }
         }
+
+        Ok(())
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum PublishFailed {
+    /// The connection was lost before the server acknowledged a publish
+    ///
+    /// The publish stays in the session state, so it is not necessarily lost.
+    #[error("The connection was lost before the publish was acknowledged")]
+    ConnectionLost,
+
+    /// The server answered a QoS 2 publish with a failure PUBREC
+    ///
+    /// The publish was dropped from the session state and its packet identifier is free again.
+    #[error("The server rejected the publish: {0:?}")]
+    Rejected(mqtt_format::v5::packets::pubrec::PubrecReasonCode),
+}
+
+/// Whether a PUBREC continues the QoS 2 flow with a PUBREL
+pub(crate) fn pubrec_accepted(reason: mqtt_format::v5::packets::pubrec::PubrecReasonCode) -> bool {
+    matches!(
+        reason,
+        mqtt_format::v5::packets::pubrec::PubrecReasonCode::Success
+            | mqtt_format::v5::packets::pubrec::PubrecReasonCode::NoMatchingSubscribers
+    )
+}
+
 enum PublishedReceiver {
     None,
     Once(PublishedQos1),
@@ -345,8 +565,8 @@ pub struct PublishedQos1 {
 }
 
 impl PublishedQos1 {
-    pub async fn acknowledged(self) {
-        self.recv.await.unwrap();
+    pub async fn acknowledged(self) -> Result<crate::packets::Puback, PublishFailed> {
+        self.recv.await.map_err(|_| PublishFailed::ConnectionLost)
     }
 }
 
@@ -356,12 +576,18 @@ pub struct PublishedQos2Received {
 }
 
 impl PublishedQos2Received {
-    pub async fn received(self) -> PublishedQos2Completed {
-        self.recv.await.unwrap();
+    pub async fn received(self) -> Result<PublishedQos2Completed, PublishFailed> {
+        let pubrec = self.recv.await.map_err(|_| PublishFailed::ConnectionLost)?;
 
-        PublishedQos2Completed {
-            recv: self.comp_recv,
+        if let mqtt_format::v5::packets::MqttPacket::Pubrec(pubrec) = pubrec.get() {
+            if !pubrec_accepted(pubrec.reason) {
+                return Err(PublishFailed::Rejected(pubrec.reason));
+            }
         }
+
+        Ok(PublishedQos2Completed {
+            recv: self.comp_recv,
+        })
     }
 }
 
@@ -370,8 +596,10 @@ pub struct PublishedQos2Completed {
 }
 
 impl PublishedQos2Completed {
-    pub async fn completed(self) {
-        self.recv.await.unwrap();
+    pub async fn completed(self) -> Result<(), PublishFailed> {
+        self.recv.await.map_err(|_| PublishFailed::ConnectionLost)?;
+
+        Ok(())
     }
 }
 
@@ -422,17 +650,515 @@ pub async fn ping(&self) -> Result<Ping, ()> {
         inner.outstanding_callbacks.add_ping_req(sender);
 
         conn_state.conn_write.send(packet).await.map_err(drop)?;
+        inner.emit_event(ClientEvent::PingSent);
 
         Ok(Ping { recv })
     }
+
+    /// Whether the server answers a PINGREQ within `timeout`
+    ///
+    /// Returns `false` when not connected. The connection stays open either way, it is up to the
+    /// caller to disconnect from a server that stopped answering.
+    pub async fn is_alive(&self, timeout: Duration) -> bool {
+        let Ok(ping) = self.ping().await else {
+            return false;
+        };
+
+        let timeout = futures_timer::Delay::new(timeout);
+        match futures::future::select(ping.recv, timeout).await {
+            futures::future::Either::Left((response, _)) => response.is_ok(),
+            futures::future::Either::Right(((), _)) => false,
+        }
+    }
+
+    /// The identifiers of the publishes the server did not acknowledge yet, in send order
+    ///
+    /// Empty if the client has no session.
+    pub async fn outstanding_packet_ids(&self) -> Vec<PacketIdentifier> {
+        self.inner
+            .lock()
+            .await
+            .session_state
+            .as_ref()
+            .map(|sess_state| {
+                sess_state
+                    .outstanding_packets
+                    .iter_in_send_order()
+                    .map(|(ident, _)| ident)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 pub struct Ping {
     recv: futures::channel::oneshot::Receiver<()>,
 }
 
+#[cfg(feature = "unstable-raw")]
+#[derive(Debug, thiserror::Error)]
+pub enum SendRawError {
+    #[error("The client is not connected")]
+    NotConnected,
+
+    #[error("Could not send the packet")]
+    Send(#[source] crate::codecs::MqttPacketCodecError),
+}
+
+#[cfg(feature = "unstable-raw")]
+impl MqttClient {
+    /// Write `packet` to the connection as is
+    ///
+    /// None of the usual bookkeeping happens: no packet identifier is reserved, nothing is
+    /// tracked for acknowledgement and answers of the server are handled like unsolicited ones.
+    /// Sending a packet the session does not expect can break the connection.
+    pub async fn send_raw(
+        &self,
+        packet: mqtt_format::v5::packets::MqttPacket<'_>,
+    ) -> Result<(), SendRawError> {
+        let mut inner = self.inner.lock().await;
+
+        let Some(conn_state) = inner.connection_state.as_mut() else {
+            tracing::error!("No connection state found");
+            return Err(SendRawError::NotConnected);
+        };
+
+        conn_state
+            .conn_write
+            .send(packet)
+            .await
+            .map_err(SendRawError::Send)
+    }
+}
+
 impl Ping {
     pub async fn response(self) {
         self.recv.await.unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use std::time::Duration;
+
+    use mqtt_format::v5::packets::connack::ConnackProperties;
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::puback::MPuback;
+    use mqtt_format::v5::packets::puback::PubackProperties;
+    use mqtt_format::v5::packets::puback::PubackReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::MaximumQualityOfService;
+    use mqtt_format::v5::qos::QualityOfService as FormatQualityOfService;
+    use mqtt_format::v5::variable_header::ContentType;
+    use mqtt_format::v5::variable_header::MaximumQoS;
+    use mqtt_format::v5::variable_header::MessageExpiryInterval;
+    use mqtt_format::v5::variable_header::RetainAvailable;
+
+    use super::PublishFailed;
+    use super::PublishQos1;
+    use super::PublishQos2;
+    use crate::client::connect::DisconnectInfo;
+    use crate::client::MqttClient;
+    use crate::packet_identifier::PacketIdentifier;
+    use crate::packet_identifier::PacketIdentifierAllocator;
+    use crate::qos::QualityOfService;
+
+    #[tokio::test]
+    async fn publish_builder_sets_properties() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .publish_builder("foo/bar".try_into().unwrap())
+            .qos(QualityOfService::AtMostOnce)
+            .retain(false)
+            .content_type("text/plain".try_into().unwrap())
+            .message_expiry_interval(60)
+            .user_property("k1".try_into().unwrap(), "v1".try_into().unwrap())
+            .user_property("k2".try_into().unwrap(), "v2".try_into().unwrap())
+            .payload(b"hello".to_vec().try_into().unwrap())
+            .send()
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(publish.topic_name, "foo/bar");
+        assert_eq!(publish.payload, b"hello");
+        assert_eq!(
+            publish.properties.content_type(),
+            Some(&ContentType("text/plain"))
+        );
+        assert_eq!(
+            publish.properties.message_expiry_interval(),
+            Some(&MessageExpiryInterval(60))
+        );
+        let user_properties = publish
+            .properties
+            .user_properties()
+            .unwrap()
+            .iter()
+            .map(|up| (up.key, up.value))
+            .collect::<Vec<_>>();
+        assert_eq!(user_properties, [("k1", "v1"), ("k2", "v2")]);
+    }
+
+    fn connack_with_retain_available(available: bool) -> ConnackProperties<'static> {
+        let mut properties = ConnackProperties::new();
+        properties.retain_available = Some(RetainAvailable(available));
+        properties
+    }
+
+    #[tokio::test]
+    async fn retained_publish_succeeds_when_retain_available() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server =
+            crate::test::connect_with(&client, connack_with_retain_available(true)).await;
+
+        client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtMostOnce,
+                retain: true,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert!(publish.retain);
+    }
+
+    #[tokio::test]
+    async fn retained_publish_fails_when_retain_unavailable() {
+        let client = MqttClient::new_with_default_handlers();
+        let _server =
+            crate::test::connect_with(&client, connack_with_retain_available(false)).await;
+
+        let result = client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtMostOnce,
+                retain: true,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn absent_maximum_qos_allows_qos2() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::ExactlyOnce,
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(
+            publish.quality_of_service,
+            FormatQualityOfService::ExactlyOnce
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_above_maximum_qos_fails() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut properties = ConnackProperties::new();
+        properties.maximum_qos = Some(MaximumQoS(MaximumQualityOfService::AtLeastOnce));
+        let mut server = crate::test::connect_with(&client, properties).await;
+
+        let publish = |qos| crate::client::send::Publish {
+            topic: "foo/bar".try_into().unwrap(),
+            qos,
+            retain: false,
+            payload: b"hello".to_vec().try_into().unwrap(),
+            on_packet_recv: None,
+        };
+
+        assert!(client
+            .publish(publish(QualityOfService::ExactlyOnce))
+            .await
+            .is_err());
+
+        client
+            .publish(publish(QualityOfService::AtLeastOnce))
+            .await
+            .unwrap();
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(
+            publish.quality_of_service,
+            FormatQualityOfService::AtLeastOnce
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_qos1_sends_at_least_once() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .publish_qos1(PublishQos1 {
+                topic: "foo/bar".try_into().unwrap(),
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(
+            publish.quality_of_service,
+            FormatQualityOfService::AtLeastOnce
+        );
+        assert!(publish.packet_identifier.is_some());
+    }
+
+    /// Only ever hands out the packet identifiers 1 and 2
+    struct TwoPacketIdentifiers;
+
+    impl PacketIdentifierAllocator for TwoPacketIdentifiers {
+        fn allocate(
+            &mut self,
+            in_use: &dyn Fn(PacketIdentifier) -> bool,
+        ) -> Option<PacketIdentifier> {
+            [1, 2]
+                .into_iter()
+                .map(|ident| PacketIdentifier::from(NonZeroU16::new(ident).unwrap()))
+                .find(|ident| !in_use(*ident))
+        }
+    }
+
+    #[tokio::test]
+    async fn exhausted_packet_identifiers_are_reused_after_acknowledgement() {
+        let client = MqttClient::new_with_default_handlers();
+        let (transport, mut server) = crate::test::make_transport();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_packet_identifier_allocator(TwoPacketIdentifiers);
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        tokio::spawn(connected.unwrap().background_task);
+
+        let publish = || crate::client::send::Publish {
+            topic: "foo/bar".try_into().unwrap(),
+            qos: QualityOfService::AtLeastOnce,
+            retain: false,
+            payload: b"hello".to_vec().try_into().unwrap(),
+            on_packet_recv: None,
+        };
+        let mut sent_identifiers = Vec::new();
+        let mut receive_identifier = |packet: crate::packets::MqttPacket| {
+            let FormatMqttPacket::Publish(publish) = packet.get() else {
+                panic!("Expected a PUBLISH, got: {packet:?}");
+            };
+            sent_identifiers.push(publish.packet_identifier.unwrap().0.get());
+        };
+
+        let first = client.publish(publish()).await.unwrap();
+        receive_identifier(server.receive().await);
+        let _second = client.publish(publish()).await.unwrap();
+        receive_identifier(server.receive().await);
+        assert!(client.publish(publish()).await.is_err());
+
+        server
+            .send(FormatMqttPacket::Puback(MPuback {
+                packet_identifier: mqtt_format::v5::variable_header::PacketIdentifier(
+                    NonZeroU16::MIN,
+                ),
+                reason: PubackReasonCode::Success,
+                properties: PubackProperties::new(),
+            }))
+            .await;
+        first.acknowledged().await.unwrap();
+
+        let _third = client.publish(publish()).await.unwrap();
+        receive_identifier(server.receive().await);
+        assert_eq!(sent_identifiers, [1, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn publish_qos2_sends_exactly_once() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .publish_qos2(PublishQos2 {
+                topic: "foo/bar".try_into().unwrap(),
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(
+            publish.quality_of_service,
+            FormatQualityOfService::ExactlyOnce
+        );
+        assert!(publish.packet_identifier.is_some());
+    }
+
+    #[tokio::test]
+    async fn lost_connection_fails_unacknowledged_publish() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let published = client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtLeastOnce,
+                retain: false,
+                payload: b"hello".to_vec().try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        assert!(matches!(packet.get(), FormatMqttPacket::Publish(_)));
+        drop(server);
+
+        assert!(matches!(
+            published.acknowledged().await,
+            Err(PublishFailed::ConnectionLost)
+        ));
+    }
+
+    #[tokio::test]
+    async fn publish_fails_once_the_write_timeout_expires() {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+        let mut connector = crate::test::make_connector(transport);
+        connector.with_write_timeout(Duration::from_millis(100));
+
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        let background = tokio::spawn(connected.unwrap().run_until_disconnect());
+
+        // The server never reads, so this does not fit into the transport buffer
+        let result = client
+            .publish(crate::client::send::Publish {
+                topic: "foo/bar".try_into().unwrap(),
+                qos: QualityOfService::AtMostOnce,
+                retain: false,
+                payload: vec![0; 4096].try_into().unwrap(),
+                on_packet_recv: None,
+            })
+            .await;
+        assert!(result.is_err());
+
+        assert!(matches!(
+            background.await.unwrap(),
+            DisconnectInfo::WriteTimeout
+        ));
+    }
+
+    #[tokio::test]
+    async fn outstanding_packet_ids_are_in_send_order() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        for payload in [b"first", b"other"] {
+            client
+                .publish_builder("foo/bar".try_into().unwrap())
+                .qos(QualityOfService::AtLeastOnce)
+                .payload(payload.to_vec().try_into().unwrap())
+                .send()
+                .await
+                .unwrap();
+            server.receive().await;
+        }
+
+        assert_eq!(
+            client.outstanding_packet_ids().await,
+            [
+                PacketIdentifier::from(NonZeroU16::new(1).unwrap()),
+                PacketIdentifier::from(NonZeroU16::new(2).unwrap())
+            ]
+        );
+    }
+
+    #[cfg(feature = "unstable-raw")]
+    #[tokio::test]
+    async fn send_raw_pingreq_gets_answered() {
+        use futures::StreamExt;
+        use mqtt_format::v5::packets::pingreq::MPingreq;
+
+        let (recv_send, mut recv) = futures::channel::mpsc::unbounded();
+        let client = MqttClient::builder()
+            .with_on_packet_recv(Box::new(move |packet| {
+                recv_send.unbounded_send(packet).unwrap();
+            }))
+            .build()
+            .await
+            .unwrap();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .send_raw(FormatMqttPacket::Pingreq(MPingreq))
+            .await
+            .unwrap();
+        let packet = server.receive().await;
+        assert!(matches!(packet.get(), FormatMqttPacket::Pingreq(_)));
+        server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+
+        let packet = recv.next().await.unwrap();
+        assert!(matches!(packet.get(), FormatMqttPacket::Pingresp(_)));
+    }
+
+    #[tokio::test]
+    async fn is_alive_reflects_ping_responses() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let (alive, _) = tokio::join!(client.is_alive(Duration::from_secs(5)), async {
+            let packet = server.receive().await;
+            assert!(matches!(packet.get(), FormatMqttPacket::Pingreq(_)));
+            server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+        });
+        assert!(alive);
+
+        // The server ignores the next PINGREQ
+        let (alive, _) = tokio::join!(client.is_alive(Duration::from_millis(50)), async {
+            let packet = server.receive().await;
+            assert!(matches!(packet.get(), FormatMqttPacket::Pingreq(_)));
+        });
+        assert!(!alive);
+
+        // The ignored ping must not swallow the answer to the next one
+        let (alive, _) = tokio::join!(client.is_alive(Duration::from_secs(5)), async {
+            let packet = server.receive().await;
+            assert!(matches!(packet.get(), FormatMqttPacket::Pingreq(_)));
+            server.send(FormatMqttPacket::Pingresp(MPingresp)).await;
+        });
+        assert!(alive);
+    }
+}
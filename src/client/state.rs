@@ -5,6 +5,7 @@
 //
 
 use std::num::NonZeroU16;
+use std::time::Duration;
 
 use futures::SinkExt;
 use tokio_util::codec::FramedRead;
@@ -14,27 +15,55 @@
 use crate::codecs::MqttPacketCodecError;
 use crate::keep_alive::KeepAlive;
 use crate::packet_identifier::PacketIdentifier;
+use crate::packet_identifier::PacketIdentifierAllocator;
+use crate::qos::QualityOfService;
 use crate::string::MqttString;
 use crate::transport::MqttConnection;
 
 pub(super) struct TransportWriter {
     conn: FramedWrite<tokio::io::WriteHalf<MqttConnection>, MqttPacketCodec>,
     notify: futures::channel::mpsc::Sender<()>,
+    write_timeout: Option<Duration>,
+    /// Tells the background task to end the connection, taken once a write timed out
+    teardown: Option<futures::channel::oneshot::Sender<()>>,
 }
 
 impl TransportWriter {
     pub(super) fn new(
         conn: FramedWrite<tokio::io::WriteHalf<MqttConnection>, MqttPacketCodec>,
         notify: futures::channel::mpsc::Sender<()>,
+        write_timeout: Option<Duration>,
+        teardown: futures::channel::oneshot::Sender<()>,
     ) -> Self {
-        Self { conn, notify }
+        Self {
+            conn,
+            notify,
+            write_timeout,
+            teardown: Some(teardown),
+        }
     }
 
     pub(super) async fn send(
         &mut self,
         packet: mqtt_format::v5::packets::MqttPacket<'_>,
     ) -> Result<(), MqttPacketCodecError> {
-        self.conn.send(packet).await?;
+        let sending = self.conn.send(packet);
+        match self.write_timeout {
+            None => sending.await?,
+            Some(write_timeout) => {
+                let timeout = futures_timer::Delay::new(write_timeout);
+                match futures::future::select(sending, timeout).await {
+                    futures::future::Either::Left((sent, _)) => sent?,
+                    futures::future::Either::Right(((), _)) => {
+                        // A partially written packet leaves the connection unusable
+                        if let Some(teardown) = self.teardown.take() {
+                            let _ = teardown.send(());
+                        }
+                        return Err(MqttPacketCodecError::WriteTimeout);
+                    }
+                }
+            }
+        }
         if let Err(e) = self.notify.try_send(()) {
             if e.is_full() {
                 // This is fine, we are already notifying of a send
@@ -46,6 +75,11 @@ pub(super) async fn send(
 
         Ok(())
     }
+
+    /// Flush outstanding packets and shut down the writing side of the transport
+    pub(super) async fn close(&mut self) -> Result<(), MqttPacketCodecError> {
+        self.conn.close().await
+    }
 }
 
 #[allow(unused)]
@@ -55,6 +89,12 @@ pub(super) struct ConnectState {
     pub(super) maximum_qos: Option<mqtt_format::v5::qos::MaximumQualityOfService>,
     pub(super) retain_available: Option<bool>,
     pub(super) topic_alias_maximum: Option<u16>,
+    /// The Topic Alias Maximum the client sent in its CONNECT, zero if it sent none
+    pub(super) advertised_topic_alias_maximum: u16,
+    /// Whether the CONNECT allowed reason strings and user properties on all packets
+    pub(super) requested_problem_information: bool,
+    /// The Authentication Method of the CONNECT, a re-authentication has to use the same one
+    pub(super) authentication_method: Option<String>,
     pub(super) maximum_packet_size: Option<u32>,
     pub(super) conn_write: TransportWriter,
 
@@ -62,14 +102,37 @@ pub(super) struct ConnectState {
         FramedRead<tokio::io::ReadHalf<MqttConnection>, MqttPacketCodec>,
     >,
 
-    pub(super) next_packet_identifier: std::num::NonZeroU16,
+    pub(super) packet_identifiers: Box<dyn PacketIdentifierAllocator>,
     pub(crate) keep_alive: KeepAlive,
 }
 
+impl ConnectState {
+    /// The highest QoS the server accepts publishes with
+    ///
+    /// A CONNACK without Maximum QoS means the server supports all of them.
+    pub(super) fn effective_maximum_qos(&self) -> QualityOfService {
+        match self.maximum_qos {
+            None => QualityOfService::ExactlyOnce,
+            Some(mqtt_format::v5::qos::MaximumQualityOfService::AtMostOnce) => {
+                QualityOfService::AtMostOnce
+            }
+            Some(mqtt_format::v5::qos::MaximumQualityOfService::AtLeastOnce) => {
+                QualityOfService::AtLeastOnce
+            }
+        }
+    }
+}
+
 pub(super) struct SessionState {
     #[allow(unused)]
     pub(super) client_identifier: MqttString,
     pub(super) outstanding_packets: OutstandingPackets,
+    /// Identifiers of received QoS 2 publishes for which no PUBREL arrived yet
+    pub(super) awaiting_pubrel: std::collections::BTreeSet<PacketIdentifier>,
+    /// Seconds the server keeps the session after the connection closed
+    pub(super) session_expiry_interval: u32,
+    /// The topic filters the server granted a subscription for, with the granted QoS
+    pub(super) subscriptions: std::collections::BTreeMap<MqttString, QualityOfService>,
 }
 
 pub(super) struct OutstandingPackets {
@@ -78,6 +141,10 @@ pub(super) struct OutstandingPackets {
         std::collections::BTreeMap<PacketIdentifier, crate::packets::MqttPacket>,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Packet identifier {} is already used by an outstanding packet", .0)]
+pub(super) struct PacketIdentifierInUse(pub(super) PacketIdentifier);
+
 impl OutstandingPackets {
     pub fn empty() -> Self {
         Self {
@@ -86,16 +153,27 @@ pub fn empty() -> Self {
         }
     }
 
-    pub fn insert(&mut self, ident: PacketIdentifier, packet: crate::packets::MqttPacket) {
+    /// Track a packet until it is acknowledged
+    ///
+    /// Fails without touching the already outstanding packet if `ident` is in use.
+    pub fn insert(
+        &mut self,
+        ident: PacketIdentifier,
+        packet: crate::packets::MqttPacket,
+    ) -> Result<(), PacketIdentifierInUse> {
         debug_assert_eq!(
             self.packet_ident_order.len(),
             self.outstanding_packets.len()
         );
 
-        self.packet_ident_order.push(ident);
-        let removed = self.outstanding_packets.insert(ident, packet);
-
-        debug_assert!(removed.is_none());
+        match self.outstanding_packets.entry(ident) {
+            std::collections::btree_map::Entry::Occupied(_) => Err(PacketIdentifierInUse(ident)),
+            std::collections::btree_map::Entry::Vacant(entry) => {
+                entry.insert(packet);
+                self.packet_ident_order.push(ident);
+                Ok(())
+            }
+        }
     }
 
     pub fn update_by_id(&mut self, ident: PacketIdentifier, packet: crate::packets::MqttPacket) {
@@ -113,7 +191,6 @@ pub fn exists_outstanding_packet(&self, ident: PacketIdentifier) -> bool {
         self.outstanding_packets.contains_key(&ident)
     }
 
-    #[allow(unused)]
     pub fn iter_in_send_order(
         &self,
     ) -> impl Iterator<Item = (PacketIdentifier, &crate::packets::MqttPacket)> {
@@ -133,3 +210,41 @@ pub fn remove_by_id(&mut self, id: PacketIdentifier) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v5::packets::pingreq::MPingreq;
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use tokio_util::bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+    use tokio_util::codec::Encoder;
+
+    use super::OutstandingPackets;
+    use crate::codecs::MqttPacketCodec;
+    use crate::packet_identifier::PacketIdentifier;
+    use crate::packets::MqttPacket;
+
+    fn packet(packet: FormatMqttPacket<'_>) -> MqttPacket {
+        let mut bytes = BytesMut::new();
+        MqttPacketCodec.encode(packet, &mut bytes).unwrap();
+        MqttPacketCodec.decode(&mut bytes).unwrap().unwrap()
+    }
+
+    #[test]
+    fn insert_reports_identifier_collision() {
+        let ident = PacketIdentifier::from(std::num::NonZeroU16::MIN);
+        let mut outstanding = OutstandingPackets::empty();
+
+        outstanding
+            .insert(ident, packet(FormatMqttPacket::Pingreq(MPingreq)))
+            .unwrap();
+        assert!(outstanding
+            .insert(ident, packet(FormatMqttPacket::Pingresp(MPingresp)))
+            .is_err());
+
+        let kept = outstanding.iter_in_send_order().collect::<Vec<_>>();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(*kept[0].1.get(), FormatMqttPacket::Pingreq(MPingreq));
+    }
+}
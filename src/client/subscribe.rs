@@ -0,0 +1,346 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+use mqtt_format::v5::packets::subscribe::MSubscribe;
+use mqtt_format::v5::packets::subscribe::RetainHandling;
+use mqtt_format::v5::packets::subscribe::SubscribeProperties;
+use mqtt_format::v5::packets::subscribe::Subscription;
+use mqtt_format::v5::packets::subscribe::SubscriptionOptions;
+use mqtt_format::v5::packets::subscribe::Subscriptions;
+
+use super::send::get_next_packet_ident;
+use super::send::PacketIdentifierExhausted;
+use super::MqttClient;
+use crate::codecs::MqttPacketCodecError;
+use crate::packets::suback::SubackReasonCode;
+use crate::qos::QualityOfService;
+use crate::string::MqttString;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubscribeError {
+    #[error("The client is not connected")]
+    NotConnected,
+
+    #[error("A SUBSCRIBE needs at least one topic filter")]
+    NoTopicFilters,
+
+    #[error(transparent)]
+    PacketIdentifierExhausted(#[from] PacketIdentifierExhausted),
+
+    #[error("Could not send the SUBSCRIBE")]
+    Send(#[source] MqttPacketCodecError),
+
+    #[error("The connection ended before the server acknowledged the SUBSCRIBE")]
+    Aborted,
+
+    /// The server refused some topic filters, listed with the reason it gave
+    ///
+    /// The other topic filters of the same SUBSCRIBE were granted and are active.
+    #[error("The server rejected {} of the topic filters", .0.len())]
+    Rejected(Vec<(MqttString, SubackReasonCode)>),
+}
+
+impl MqttClient {
+    /// Subscribe to the given topic filters, each with the maximum QoS it should be delivered with
+    ///
+    /// Resolves once the server answered with a SUBACK. If the server refused any of the topic
+    /// filters, this returns [`SubscribeError::Rejected`].
+    pub async fn subscribe(
+        &self,
+        subscriptions: &[(MqttString, QualityOfService)],
+    ) -> Result<Subscribed, SubscribeError> {
+        let format_subscriptions = subscriptions
+            .iter()
+            .map(|(topic_filter, qos)| Subscription {
+                topic_filter: topic_filter.as_ref(),
+                options: SubscriptionOptions {
+                    quality_of_service: (*qos).into(),
+                    no_local: false,
+                    retain_as_published: false,
+                    retain_handling: RetainHandling::SendRetainedMessagesAlways,
+                },
+            })
+            .collect::<Vec<_>>();
+        let format_subscriptions = Subscriptions::from_slice(&format_subscriptions)
+            .ok_or(SubscribeError::NoTopicFilters)?;
+
+        let recv = {
+            let mut inner = self.inner.lock().await;
+            let inner = &mut *inner;
+
+            let (Some(conn_state), Some(sess_state)) =
+                (&mut inner.connection_state, &inner.session_state)
+            else {
+                tracing::error!("No connection state found");
+                return Err(SubscribeError::NotConnected);
+            };
+
+            let packet_identifier = get_next_packet_ident(
+                conn_state.packet_identifiers.as_mut(),
+                &sess_state.outstanding_packets,
+                &inner.outstanding_callbacks,
+            )?;
+
+            let packet = mqtt_format::v5::packets::MqttPacket::Subscribe(MSubscribe {
+                packet_identifier: packet_identifier.into(),
+                properties: SubscribeProperties::new(),
+                subscriptions: format_subscriptions,
+            });
+
+            let (on_suback, recv) = futures::channel::oneshot::channel();
+            inner.outstanding_callbacks.add_subscribe(
+                packet_identifier,
+                SubscribeCallback {
+                    topic_filters: subscriptions
+                        .iter()
+                        .map(|(topic_filter, _)| topic_filter.clone())
+                        .collect(),
+                    on_suback,
+                },
+            );
+
+            conn_state
+                .conn_write
+                .send(packet)
+                .await
+                .map_err(SubscribeError::Send)?;
+
+            recv
+        };
+
+        let subscribed = recv.await.map_err(|_| SubscribeError::Aborted)?;
+
+        let rejected = subscriptions
+            .iter()
+            .zip(subscribed.reason_codes())
+            .filter(|(_, reason)| granted_qos(**reason).is_none())
+            .map(|((topic_filter, _), reason)| (topic_filter.clone(), *reason))
+            .collect::<Vec<_>>();
+        if !rejected.is_empty() {
+            return Err(SubscribeError::Rejected(rejected));
+        }
+
+        Ok(subscribed)
+    }
+
+    /// The topic filters the server granted a subscription for, with the granted QoS
+    ///
+    /// Empty if the client is not connected.
+    pub async fn active_subscriptions(&self) -> Vec<(MqttString, QualityOfService)> {
+        self.inner
+            .lock()
+            .await
+            .session_state
+            .as_ref()
+            .map(|sess_state| {
+                sess_state
+                    .subscriptions
+                    .iter()
+                    .map(|(topic_filter, qos)| (topic_filter.clone(), *qos))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub(crate) struct SubscribeCallback {
+    /// The topic filters in the order they were sent, as the SUBACK only contains reason codes
+    pub(crate) topic_filters: Vec<MqttString>,
+    pub(crate) on_suback: futures::channel::oneshot::Sender<Subscribed>,
+}
+
+/// The answer of the server to a SUBSCRIBE
+#[derive(Debug)]
+pub struct Subscribed {
+    pub(crate) reasons: Vec<SubackReasonCode>,
+    pub(crate) granted: Vec<(MqttString, QualityOfService)>,
+}
+
+impl Subscribed {
+    /// The reason codes of the SUBACK, one per topic filter in the order they were given
+    pub fn reason_codes(&self) -> &[SubackReasonCode] {
+        &self.reasons
+    }
+
+    /// The topic filters the server granted, with the QoS it granted them with
+    ///
+    /// The server may grant a lower QoS than was asked for.
+    pub fn granted_qos(&self) -> &[(MqttString, QualityOfService)] {
+        &self.granted
+    }
+}
+
+/// The QoS the server granted a subscription with, if it did
+pub(crate) fn granted_qos(reason: SubackReasonCode) -> Option<QualityOfService> {
+    match reason {
+        SubackReasonCode::GrantedQoS0 => Some(QualityOfService::AtMostOnce),
+        SubackReasonCode::GrantedQoS1 => Some(QualityOfService::AtLeastOnce),
+        SubackReasonCode::GrantedQoS2 => Some(QualityOfService::ExactlyOnce),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v5::packets::suback::MSuback;
+    use mqtt_format::v5::packets::suback::SubackProperties;
+    use mqtt_format::v5::packets::suback::SubackReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::QualityOfService as FormatQualityOfService;
+
+    use super::SubscribeError;
+    use crate::client::MqttClient;
+    use crate::qos::QualityOfService;
+    use crate::string::MqttString;
+
+    #[tokio::test]
+    async fn active_subscriptions_lists_granted_filters() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let subscriptions = [
+            ("foo/bar".try_into().unwrap(), QualityOfService::ExactlyOnce),
+            ("foo/#".try_into().unwrap(), QualityOfService::AtLeastOnce),
+        ];
+        let (subscribed, _) = tokio::join!(client.subscribe(&subscriptions), async {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Subscribe(subscribe) = packet.get() else {
+                panic!("Expected a SUBSCRIBE, got: {packet:?}");
+            };
+            let requested = subscribe
+                .subscriptions
+                .iter()
+                .map(|sub| (sub.topic_filter, sub.options.quality_of_service))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                requested,
+                [
+                    ("foo/bar", FormatQualityOfService::ExactlyOnce),
+                    ("foo/#", FormatQualityOfService::AtLeastOnce)
+                ]
+            );
+
+            // The server downgrades the first subscription
+            server
+                .send(FormatMqttPacket::Suback(MSuback {
+                    packet_identifier: subscribe.packet_identifier,
+                    properties: SubackProperties::new(),
+                    reasons: &[SubackReasonCode::GrantedQoS1, SubackReasonCode::GrantedQoS1],
+                }))
+                .await;
+        });
+        subscribed.unwrap();
+
+        let mut active = client.active_subscriptions().await;
+        active.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            active,
+            [
+                (
+                    MqttString::try_from("foo/#").unwrap(),
+                    QualityOfService::AtLeastOnce
+                ),
+                (
+                    MqttString::try_from("foo/bar").unwrap(),
+                    QualityOfService::AtLeastOnce
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn downgraded_qos_is_visible() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let subscriptions = [("foo/bar".try_into().unwrap(), QualityOfService::ExactlyOnce)];
+        let (subscribed, _) = tokio::join!(client.subscribe(&subscriptions), async {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Subscribe(subscribe) = packet.get() else {
+                panic!("Expected a SUBSCRIBE, got: {packet:?}");
+            };
+
+            server
+                .send(FormatMqttPacket::Suback(MSuback {
+                    packet_identifier: subscribe.packet_identifier,
+                    properties: SubackProperties::new(),
+                    reasons: &[SubackReasonCode::GrantedQoS1],
+                }))
+                .await;
+        });
+
+        assert_eq!(
+            subscribed.unwrap().granted_qos(),
+            [(
+                MqttString::try_from("foo/bar").unwrap(),
+                QualityOfService::AtLeastOnce
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_topic_filters_are_reported() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let subscriptions = [
+            (
+                "public/#".try_into().unwrap(),
+                QualityOfService::AtLeastOnce,
+            ),
+            (
+                "secret/#".try_into().unwrap(),
+                QualityOfService::AtLeastOnce,
+            ),
+        ];
+        let (subscribed, _) = tokio::join!(client.subscribe(&subscriptions), async {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Subscribe(subscribe) = packet.get() else {
+                panic!("Expected a SUBSCRIBE, got: {packet:?}");
+            };
+
+            server
+                .send(FormatMqttPacket::Suback(MSuback {
+                    packet_identifier: subscribe.packet_identifier,
+                    properties: SubackProperties::new(),
+                    reasons: &[
+                        SubackReasonCode::GrantedQoS1,
+                        SubackReasonCode::NotAuthorized,
+                    ],
+                }))
+                .await;
+        });
+
+        let Err(SubscribeError::Rejected(rejected)) = subscribed else {
+            panic!("Expected the subscription to be rejected, got: {subscribed:?}");
+        };
+        assert_eq!(
+            rejected,
+            [(
+                MqttString::try_from("secret/#").unwrap(),
+                SubackReasonCode::NotAuthorized
+            )]
+        );
+        assert_eq!(
+            client.active_subscriptions().await,
+            [(
+                MqttString::try_from("public/#").unwrap(),
+                QualityOfService::AtLeastOnce
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribe_without_topic_filters_fails() {
+        let client = MqttClient::new_with_default_handlers();
+        let _server = crate::test::connect(&client).await;
+
+        assert!(matches!(
+            client.subscribe(&[]).await,
+            Err(SubscribeError::NoTopicFilters)
+        ));
+    }
+}
@@ -0,0 +1,177 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+use mqtt_format::v5::packets::unsubscribe::MUnsubscribe;
+use mqtt_format::v5::packets::unsubscribe::UnsubscribeProperties;
+use mqtt_format::v5::packets::unsubscribe::Unsubscription;
+use mqtt_format::v5::packets::unsubscribe::Unsubscriptions;
+
+use super::send::get_next_packet_ident;
+use super::send::PacketIdentifierExhausted;
+use super::MqttClient;
+use crate::codecs::MqttPacketCodecError;
+use crate::packets::unsuback::UnsubackReasonCode;
+use crate::string::MqttString;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("The client is not connected")]
+    NotConnected,
+
+    #[error("An UNSUBSCRIBE needs at least one topic filter")]
+    NoTopicFilters,
+
+    #[error(transparent)]
+    PacketIdentifierExhausted(#[from] PacketIdentifierExhausted),
+
+    #[error("Could not send the UNSUBSCRIBE")]
+    Send(#[source] MqttPacketCodecError),
+
+    #[error("The connection ended before the server acknowledged the UNSUBSCRIBE")]
+    Aborted,
+}
+
+impl MqttClient {
+    /// Unsubscribe from the given topic filters
+    ///
+    /// Resolves once the server answered with an UNSUBACK.
+    pub async fn unsubscribe(
+        &self,
+        topic_filters: &[MqttString],
+    ) -> Result<Unsubscribed, UnsubscribeError> {
+        let format_unsubscriptions = topic_filters
+            .iter()
+            .map(|topic_filter| Unsubscription {
+                topic_filter: topic_filter.as_ref(),
+            })
+            .collect::<Vec<_>>();
+        let unsubscriptions = Unsubscriptions::from_slice(&format_unsubscriptions)
+            .ok_or(UnsubscribeError::NoTopicFilters)?;
+
+        let recv = {
+            let mut inner = self.inner.lock().await;
+            let inner = &mut *inner;
+
+            let (Some(conn_state), Some(sess_state)) =
+                (&mut inner.connection_state, &inner.session_state)
+            else {
+                tracing::error!("No connection state found");
+                return Err(UnsubscribeError::NotConnected);
+            };
+
+            let packet_identifier = get_next_packet_ident(
+                conn_state.packet_identifiers.as_mut(),
+                &sess_state.outstanding_packets,
+                &inner.outstanding_callbacks,
+            )?;
+
+            let packet = mqtt_format::v5::packets::MqttPacket::Unsubscribe(MUnsubscribe {
+                packet_identifier: packet_identifier.into(),
+                properties: UnsubscribeProperties::new(),
+                unsubscriptions,
+            });
+
+            let (on_unsuback, recv) = futures::channel::oneshot::channel();
+            inner.outstanding_callbacks.add_unsubscribe(
+                packet_identifier,
+                UnsubscribeCallback {
+                    topic_filters: topic_filters.to_vec(),
+                    on_unsuback,
+                },
+            );
+
+            conn_state
+                .conn_write
+                .send(packet)
+                .await
+                .map_err(UnsubscribeError::Send)?;
+
+            recv
+        };
+
+        recv.await.map_err(|_| UnsubscribeError::Aborted)
+    }
+}
+
+pub(crate) struct UnsubscribeCallback {
+    /// The topic filters in the order they were sent, as the UNSUBACK only contains reason codes
+    pub(crate) topic_filters: Vec<MqttString>,
+    pub(crate) on_unsuback: futures::channel::oneshot::Sender<Unsubscribed>,
+}
+
+/// The answer of the server to an UNSUBSCRIBE
+#[derive(Debug)]
+pub struct Unsubscribed {
+    pub(crate) reasons: Vec<UnsubackReasonCode>,
+}
+
+impl Unsubscribed {
+    /// The reason codes of the UNSUBACK, one per topic filter in the order they were given
+    pub fn reason_codes(&self) -> &[UnsubackReasonCode] {
+        &self.reasons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v5::packets::unsuback::MUnsuback;
+    use mqtt_format::v5::packets::unsuback::UnsubackProperties;
+    use mqtt_format::v5::packets::unsuback::UnsubackReasonCode;
+    use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+
+    use super::UnsubscribeError;
+    use crate::client::MqttClient;
+
+    #[tokio::test]
+    async fn unsubscribe_surfaces_reason_codes() {
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        let topic_filters = ["foo/bar".try_into().unwrap(), "foo/#".try_into().unwrap()];
+        let (unsubscribed, _) = tokio::join!(client.unsubscribe(&topic_filters), async {
+            let packet = server.receive().await;
+            let FormatMqttPacket::Unsubscribe(unsubscribe) = packet.get() else {
+                panic!("Expected an UNSUBSCRIBE, got: {packet:?}");
+            };
+            let filters = unsubscribe
+                .unsubscriptions
+                .iter()
+                .map(|unsub| unsub.topic_filter)
+                .collect::<Vec<_>>();
+            assert_eq!(filters, ["foo/bar", "foo/#"]);
+
+            server
+                .send(FormatMqttPacket::Unsuback(MUnsuback {
+                    packet_identifier: unsubscribe.packet_identifier,
+                    properties: UnsubackProperties::new(),
+                    reasons: &[
+                        UnsubackReasonCode::Success,
+                        UnsubackReasonCode::NoSubscriptionExisted,
+                    ],
+                }))
+                .await;
+        });
+
+        assert_eq!(
+            unsubscribed.unwrap().reason_codes(),
+            [
+                UnsubackReasonCode::Success,
+                UnsubackReasonCode::NoSubscriptionExisted
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_without_topic_filters_fails() {
+        let client = MqttClient::new_with_default_handlers();
+        let _server = crate::test::connect(&client).await;
+
+        assert!(matches!(
+            client.unsubscribe(&[]).await,
+            Err(UnsubscribeError::NoTopicFilters)
+        ));
+    }
+}
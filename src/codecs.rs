@@ -21,11 +21,21 @@ pub enum MqttPacketCodecError {
     #[error("An error occured while writing to a buffer")]
     Writer(#[from] MqttWriterError),
 
+    /// The remaining length of a frame is not a valid variable byte integer
+    ///
+    /// Unlike a frame that is merely incomplete, more bytes can not fix this.
     #[error("A protocol error occurred")]
     Protocol,
 
+    #[error("Refusing to send a packet that violates {0}")]
+    InvalidPacket(&'static str),
+
+    /// A frame was received in full, but its contents are not a valid packet
     #[error("Could not parse during decoding due to: {:?}", .0)]
     Parsing(winnow::error::ErrMode<winnow::error::ContextError>),
+
+    #[error("Writing a packet did not finish within the write timeout")]
+    WriteTimeout,
 }
 
 pub(crate) struct MqttPacketCodec;
@@ -35,6 +45,11 @@ impl Decoder for MqttPacketCodec {
 
     type Error = MqttPacketCodecError;
 
+    /// Decode the first complete packet in `src`
+    ///
+    /// Only the bytes of that packet are split off, so `FramedRead` keeps calling this until all
+    /// complete packets of a single read are drained. A partial packet stays in the buffer and
+    /// `Ok(None)` signals that more bytes are needed.
     fn decode(
         &mut self,
         src: &mut tokio_util::bytes::BytesMut,
@@ -58,7 +73,7 @@ fn decode(
                     src.reserve(1);
                     return Ok(None);
                 }
-                _ => {
+                Err(winnow::error::ErrMode::Backtrack(_) | winnow::error::ErrMode::Cut(_)) => {
                     return Err(MqttPacketCodecError::Protocol);
                 }
             };
@@ -93,6 +108,15 @@ fn encode(
         packet: FormatMqttPacket<'_>,
         dst: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
+        // Checked before writing anything, so a rejected packet leaves no partial bytes behind
+        if let FormatMqttPacket::Publish(publish) = &packet {
+            if publish.quality_of_service == mqtt_format::v5::qos::QualityOfService::AtMostOnce
+                && publish.packet_identifier.is_some()
+            {
+                return Err(MqttPacketCodecError::InvalidPacket("MQTT-2.2.1-2"));
+            }
+        }
+
         let size = packet.binary_size() as usize;
         dst.reserve(size);
 
@@ -107,15 +131,29 @@ fn encode(
 
 #[cfg(test)]
 mod tests {
+    use std::num::NonZeroU16;
+
     use futures::SinkExt;
     use futures::StreamExt;
     use mqtt_format::v5::packets::connect::MConnect;
+    use mqtt_format::v5::packets::disconnect::DisconnectProperties;
+    use mqtt_format::v5::packets::disconnect::DisconnectReasonCode;
+    use mqtt_format::v5::packets::disconnect::MDisconnect;
     use mqtt_format::v5::packets::pingreq::MPingreq;
+    use mqtt_format::v5::packets::pingresp::MPingresp;
+    use mqtt_format::v5::packets::publish::MPublish;
+    use mqtt_format::v5::packets::publish::PublishProperties;
     use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+    use mqtt_format::v5::qos::QualityOfService;
+    use mqtt_format::v5::variable_header::PacketIdentifier;
+    use tokio_util::bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+    use tokio_util::codec::Encoder;
     use tokio_util::codec::Framed;
     use tokio_util::compat::TokioAsyncReadCompatExt;
 
     use super::MqttPacketCodec;
+    use super::MqttPacketCodecError;
     use crate::transport::MqttConnection;
 
     #[tokio::test]
@@ -164,4 +202,128 @@ async fn test_connect_codec() {
 
         assert_eq!(packet, *recv_packet.get());
     }
+
+    #[test]
+    fn qos0_publish_with_packet_identifier_is_rejected() {
+        let packet = FormatMqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtMostOnce,
+            retain: false,
+            topic_name: "foo/bar",
+            packet_identifier: Some(PacketIdentifier(NonZeroU16::MIN)),
+            properties: PublishProperties::new(),
+            payload: b"hello",
+        });
+
+        let mut dst = BytesMut::new();
+        assert!(matches!(
+            MqttPacketCodec.encode(packet, &mut dst),
+            Err(MqttPacketCodecError::InvalidPacket(_))
+        ));
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn raw_bytes_reparse_to_the_same_packet() {
+        let packet = FormatMqttPacket::Disconnect(MDisconnect {
+            reason_code: DisconnectReasonCode::ServerShuttingDown,
+            properties: DisconnectProperties::new(),
+        });
+
+        let mut src = BytesMut::new();
+        MqttPacketCodec.encode(packet.clone(), &mut src).unwrap();
+        let encoded = src.clone().freeze();
+        let decoded = MqttPacketCodec.decode(&mut src).unwrap().unwrap();
+
+        assert_eq!(decoded.raw_bytes(), &encoded[..]);
+        assert_eq!(
+            FormatMqttPacket::parse_complete(decoded.raw_bytes()).unwrap(),
+            packet
+        );
+    }
+
+    #[test]
+    fn decode_drains_concatenated_packets() {
+        let packets = [
+            FormatMqttPacket::Pingreq(MPingreq),
+            FormatMqttPacket::Disconnect(MDisconnect {
+                reason_code: DisconnectReasonCode::NormalDisconnection,
+                properties: DisconnectProperties::new(),
+            }),
+            FormatMqttPacket::Pingresp(MPingresp),
+        ];
+
+        let mut src = BytesMut::new();
+        for packet in packets.iter().cloned() {
+            MqttPacketCodec.encode(packet, &mut src).unwrap();
+        }
+
+        let connect = FormatMqttPacket::Connect(MConnect {
+            client_identifier: "test",
+            username: None,
+            password: None,
+            clean_start: false,
+            will: None,
+            properties: mqtt_format::v5::packets::connect::ConnectProperties::new(),
+            keep_alive: 0,
+        });
+        let mut partial = BytesMut::new();
+        MqttPacketCodec
+            .encode(connect.clone(), &mut partial)
+            .unwrap();
+        let split = partial.len() / 2;
+        src.extend_from_slice(&partial[..split]);
+
+        for packet in &packets {
+            let decoded = MqttPacketCodec.decode(&mut src).unwrap().unwrap();
+            assert_eq!(decoded.get(), packet);
+        }
+
+        assert!(MqttPacketCodec.decode(&mut src).unwrap().is_none());
+        assert_eq!(src[..], partial[..split]);
+
+        src.extend_from_slice(&partial[split..]);
+        let decoded = MqttPacketCodec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(decoded.get(), &connect);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn partial_frames_only_error_once_malformed() {
+        let packet = FormatMqttPacket::Publish(MPublish {
+            duplicate: false,
+            quality_of_service: QualityOfService::AtLeastOnce,
+            retain: false,
+            topic_name: "foo/bar",
+            packet_identifier: Some(PacketIdentifier(NonZeroU16::MIN)),
+            properties: PublishProperties::new(),
+            payload: &[0xAB; 200],
+        });
+        let mut encoded = BytesMut::new();
+        MqttPacketCodec
+            .encode(packet.clone(), &mut encoded)
+            .unwrap();
+
+        let mut src = BytesMut::new();
+        let (last, head) = encoded.split_last().unwrap();
+        for byte in head {
+            src.extend_from_slice(&[*byte]);
+            assert!(MqttPacketCodec.decode(&mut src).unwrap().is_none());
+        }
+        src.extend_from_slice(&[*last]);
+        let decoded = MqttPacketCodec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(decoded.get(), &packet);
+
+        // A remaining length may use at most four bytes, so only the fifth makes this malformed
+        let mut src = BytesMut::new();
+        for byte in [0x30, 0xFF, 0xFF, 0xFF] {
+            src.extend_from_slice(&[byte]);
+            assert!(MqttPacketCodec.decode(&mut src).unwrap().is_none());
+        }
+        src.extend_from_slice(&[0xFF]);
+        assert!(matches!(
+            MqttPacketCodec.decode(&mut src),
+            Err(MqttPacketCodecError::Protocol)
+        ));
+    }
 }
@@ -7,13 +7,33 @@
 use std::num::NonZeroU16;
 use std::time::Duration;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeepAlive {
     Disabled,
     Seconds(NonZeroU16),
 }
 
 impl KeepAlive {
+    /// A keep alive of 60 seconds, which suits most connections
+    pub const AUTO: KeepAlive = match NonZeroU16::new(60) {
+        Some(seconds) => KeepAlive::Seconds(seconds),
+        None => unreachable!(),
+    };
+
+    /// A keep alive of `duration` rounded to the nearest second
+    ///
+    /// Durations longer than the maximum of `u16::MAX` seconds are capped to it, unlike the
+    /// `TryFrom<Duration>` implementation. Only a duration that rounds to zero is an error, use
+    /// [`KeepAlive::Disabled`] to turn keep alive off.
+    pub fn from_duration_rounded(duration: Duration) -> Result<KeepAlive, KeepAliveError> {
+        let secs = duration
+            .as_secs()
+            .saturating_add(u64::from(duration.subsec_millis() >= 500));
+        let secs = u16::try_from(secs).unwrap_or(u16::MAX);
+
+        Ok(KeepAlive::Seconds(NonZeroU16::try_from(secs)?))
+    }
+
     pub(crate) fn as_u16(&self) -> u16 {
         match self {
             KeepAlive::Disabled => 0,
@@ -44,3 +64,57 @@ pub enum KeepAliveError {
     #[error("KeepAlive out of bounds, maximum is {} seconds", u16::MAX)]
     OutOfBounds,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::KeepAlive;
+    use super::KeepAliveError;
+
+    #[test]
+    fn auto_is_sixty_seconds() {
+        assert_eq!(KeepAlive::AUTO.as_u16(), 60);
+    }
+
+    #[test]
+    fn duration_is_rounded_to_seconds() {
+        let rounded = |millis| {
+            KeepAlive::from_duration_rounded(Duration::from_millis(millis))
+                .unwrap()
+                .as_u16()
+        };
+
+        assert_eq!(rounded(1_499), 1);
+        assert_eq!(rounded(1_500), 2);
+        assert_eq!(rounded(500), 1);
+        assert_eq!(rounded(30_000), 30);
+    }
+
+    #[test]
+    fn zero_duration_is_an_error() {
+        assert!(matches!(
+            KeepAlive::from_duration_rounded(Duration::ZERO),
+            Err(KeepAliveError::KeepAliveZero(_))
+        ));
+        assert!(matches!(
+            KeepAlive::from_duration_rounded(Duration::from_millis(499)),
+            Err(KeepAliveError::KeepAliveZero(_))
+        ));
+    }
+
+    #[test]
+    fn long_duration_is_capped() {
+        let keep_alive =
+            KeepAlive::from_duration_rounded(Duration::from_secs(u64::from(u16::MAX) + 1)).unwrap();
+        assert_eq!(keep_alive.as_u16(), u16::MAX);
+
+        let keep_alive = KeepAlive::from_duration_rounded(Duration::MAX).unwrap();
+        assert_eq!(keep_alive.as_u16(), u16::MAX);
+
+        assert!(matches!(
+            KeepAlive::try_from(Duration::from_secs(u64::from(u16::MAX) + 1)),
+            Err(KeepAliveError::OutOfBounds)
+        ));
+    }
+}
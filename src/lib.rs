@@ -18,6 +18,8 @@
 mod properties;
 pub mod qos;
 pub mod string;
+#[cfg(test)]
+mod test;
 pub mod topic;
 pub mod transport;
 mod util;
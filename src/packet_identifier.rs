@@ -37,3 +37,49 @@ fn from(value: std::num::NonZeroU16) -> Self {
         Self(value)
     }
 }
+
+/// Hands out the packet identifiers of outgoing packets, see
+/// [`MqttClientConnector::with_packet_identifier_allocator`]
+///
+/// [`MqttClientConnector::with_packet_identifier_allocator`]: crate::client::connect::MqttClientConnector::with_packet_identifier_allocator
+pub trait PacketIdentifierAllocator: Send {
+    /// An identifier for which `in_use` returns `false`, or `None` if there is none left
+    fn allocate(&mut self, in_use: &dyn Fn(PacketIdentifier) -> bool) -> Option<PacketIdentifier>;
+}
+
+/// Counts up from 1, wrapping around and skipping identifiers that are still in use
+#[derive(Debug, Clone)]
+pub struct SequentialPacketIdentifiers {
+    next: std::num::NonZeroU16,
+}
+
+impl Default for SequentialPacketIdentifiers {
+    fn default() -> Self {
+        Self {
+            next: std::num::NonZeroU16::MIN,
+        }
+    }
+}
+
+impl PacketIdentifierAllocator for SequentialPacketIdentifiers {
+    fn allocate(&mut self, in_use: &dyn Fn(PacketIdentifier) -> bool) -> Option<PacketIdentifier> {
+        let start = self.next;
+
+        loop {
+            let next = PacketIdentifier(self.next);
+
+            if !in_use(next) {
+                return Some(next);
+            }
+
+            self.next = self
+                .next
+                .checked_add(1)
+                .unwrap_or(std::num::NonZeroU16::MIN);
+
+            if start == self.next {
+                return None;
+            }
+        }
+    }
+}
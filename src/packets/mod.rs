@@ -70,6 +70,11 @@ impl MqttPacket {
     pub fn get(&self) -> &FormatMqttPacket<'_> {
         self.packet.get()
     }
+
+    /// The bytes of the packet as they were received, including the fixed header
+    pub fn raw_bytes(&self) -> &[u8] {
+        self.packet.backing_cart()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
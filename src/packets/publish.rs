@@ -4,6 +4,12 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+use yoke::Yoke;
+
+use super::MqttPacket;
+use super::StableBytes;
+use crate::qos::QualityOfService;
+
 crate::properties::define_properties! {
     properties_type: mqtt_format::v5::packets::publish::PublishProperties,
     anker: "_Toc3901109",
@@ -33,3 +39,72 @@ pub struct PublishProperties {
         content_type: ContentType<'i> with setter = String,
     }
 }
+
+/// A PUBLISH packet received from the server
+#[derive(Clone, Debug)]
+pub struct Publish {
+    packet: Yoke<mqtt_format::v5::packets::publish::MPublish<'static>, StableBytes>,
+}
+
+impl Publish {
+    pub(crate) fn get(&self) -> &mqtt_format::v5::packets::publish::MPublish<'_> {
+        self.packet.get()
+    }
+
+    pub fn topic(&self) -> &str {
+        self.get().topic_name
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        self.get().payload
+    }
+
+    pub fn qos(&self) -> QualityOfService {
+        self.get().quality_of_service.into()
+    }
+
+    pub fn retain(&self) -> bool {
+        self.get().retain
+    }
+
+    pub fn duplicate(&self) -> bool {
+        self.get().duplicate
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.get().properties.content_type().map(|ct| ct.0)
+    }
+
+    /// The seconds left until the message expires, as the server forwarded it
+    ///
+    /// The server subtracts the time it held on to the message from the interval the sender set.
+    pub fn message_expiry_interval(&self) -> Option<u32> {
+        self.get()
+            .properties
+            .message_expiry_interval()
+            .map(|mei| mei.0)
+    }
+
+    /// The topic the sender expects a response on
+    pub fn response_topic(&self) -> Option<&str> {
+        self.get().properties.response_topic().map(|rt| rt.0)
+    }
+
+    /// The data the sender wants back in the response, to match it with its request
+    pub fn correlation_data(&self) -> Option<&[u8]> {
+        self.get().properties.correlation_data().map(|cd| cd.0)
+    }
+}
+
+impl TryFrom<MqttPacket> for Publish {
+    type Error = ();
+
+    fn try_from(value: MqttPacket) -> Result<Self, Self::Error> {
+        let packet = value.packet.try_map_project(|p, _| match p {
+            mqtt_format::v5::packets::MqttPacket::Publish(publish) => Ok(publish),
+            _ => Err(()),
+        })?;
+
+        Ok(Publish { packet })
+    }
+}
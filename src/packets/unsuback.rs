@@ -4,6 +4,8 @@
 //   file, You can obtain one at http://mozilla.org/MPL/2.0/.
 //
 
+pub use mqtt_format::v5::packets::unsuback::UnsubackReasonCode;
+
 crate::properties::define_properties! {
     properties_type: mqtt_format::v5::packets::unsuback::UnsubackProperties,
     anker: "_Toc3901190",
@@ -7,11 +7,50 @@
 use mqtt_format::v5::integers::VARIABLE_INTEGER_MAX;
 
 #[derive(Debug)]
-pub struct MqttPayload(Vec<u8>);
+pub struct MqttPayload {
+    bytes: Vec<u8>,
+    content_type: Option<&'static str>,
+}
+
+impl MqttPayload {
+    /// Serialize `value` as JSON, the payload is then published with the `application/json`
+    /// content type
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize>(value: &T) -> Result<MqttPayload, MqttPayloadError> {
+        let bytes = serde_json::to_vec(value)?;
+
+        Ok(MqttPayload {
+            content_type: Some("application/json"),
+            ..MqttPayload::try_from(bytes)?
+        })
+    }
+
+    /// Serialize `value` as CBOR, the payload is then published with the `application/cbor`
+    /// content type
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T: serde::Serialize>(value: &T) -> Result<MqttPayload, MqttPayloadError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes)?;
+
+        Ok(MqttPayload {
+            content_type: Some("application/cbor"),
+            ..MqttPayload::try_from(bytes)?
+        })
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type
+    }
+
+    /// Whether the payload is known to be UTF-8 encoded character data
+    pub(crate) fn is_utf8(&self) -> bool {
+        self.content_type == Some("application/json")
+    }
+}
 
 impl AsRef<[u8]> for MqttPayload {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        self.bytes.as_ref()
     }
 }
 
@@ -22,6 +61,14 @@ pub enum MqttPayloadError {
         VARIABLE_INTEGER_MAX
     )]
     Length { given: usize },
+
+    #[cfg(feature = "json")]
+    #[error("Could not serialize the payload as JSON")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "cbor")]
+    #[error("Could not serialize the payload as CBOR")]
+    Cbor(#[from] ciborium::ser::Error<std::io::Error>),
 }
 
 impl TryFrom<Vec<u8>> for MqttPayload {
@@ -30,7 +77,122 @@ fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
         if value.len() > VARIABLE_INTEGER_MAX as usize {
             Err(MqttPayloadError::Length { given: value.len() })
         } else {
-            Ok(MqttPayload(value))
+            Ok(MqttPayload {
+                bytes: value,
+                content_type: None,
+            })
         }
     }
 }
+
+#[cfg(all(test, any(feature = "json", feature = "cbor")))]
+mod tests {
+    use futures::StreamExt;
+
+    use super::MqttPayload;
+    use crate::client::send::Publish;
+    use crate::client::MqttClient;
+    use crate::packets::publish::Publish as ReceivedPublish;
+    use crate::qos::QualityOfService;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    fn reading() -> Reading {
+        Reading {
+            sensor: "temperature".to_string(),
+            value: 21.5,
+        }
+    }
+
+    /// Publish the payload and let the server echo it back to the client
+    async fn round_trip(payload: MqttPayload) -> ReceivedPublish {
+        let (transport, mut server) = crate::test::make_transport();
+        let client = MqttClient::new_with_default_handlers();
+
+        let (connected, _) = tokio::join!(
+            client.connect(crate::test::make_connector(transport)),
+            server.accept_connect()
+        );
+        tokio::spawn(connected.unwrap().background_task);
+        let mut incoming = client.incoming_publishes(1).await;
+
+        client
+            .publish(Publish {
+                topic: "sensors/reading".try_into().unwrap(),
+                qos: QualityOfService::AtMostOnce,
+                retain: false,
+                payload,
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        server.send(packet.get().clone()).await;
+
+        incoming.next().await.unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_payload_round_trip() {
+        let received = round_trip(MqttPayload::json(&reading()).unwrap()).await;
+
+        assert_eq!(received.content_type(), Some("application/json"));
+        assert_eq!(
+            serde_json::from_slice::<Reading>(received.payload()).unwrap(),
+            reading()
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_publish_is_marked_as_utf8() {
+        use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+        use mqtt_format::v5::variable_header::ContentType;
+        use mqtt_format::v5::variable_header::PayloadFormatIndicator;
+
+        let client = MqttClient::new_with_default_handlers();
+        let mut server = crate::test::connect(&client).await;
+
+        client
+            .publish(Publish {
+                topic: "sensors/reading".try_into().unwrap(),
+                qos: QualityOfService::AtMostOnce,
+                retain: false,
+                payload: MqttPayload::json(&reading()).unwrap(),
+                on_packet_recv: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = server.receive().await;
+        let FormatMqttPacket::Publish(publish) = packet.get() else {
+            panic!("Expected a PUBLISH, got: {packet:?}");
+        };
+        assert_eq!(
+            publish.properties.payload_format_indicator(),
+            Some(&PayloadFormatIndicator(1))
+        );
+        assert_eq!(
+            publish.properties.content_type(),
+            Some(&ContentType("application/json"))
+        );
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn cbor_payload_round_trip() {
+        let received = round_trip(MqttPayload::cbor(&reading()).unwrap()).await;
+
+        assert_eq!(received.content_type(), Some("application/cbor"));
+        assert_eq!(
+            ciborium::from_reader::<Reading, _>(received.payload()).unwrap(),
+            reading()
+        );
+    }
+}
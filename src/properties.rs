@@ -126,6 +126,12 @@ pub struct UserProperty {
     value: MqttString,
 }
 
+impl UserProperty {
+    pub fn new(key: MqttString, value: MqttString) -> Self {
+        Self { key, value }
+    }
+}
+
 pub(crate) trait FormatProperty {
     type Inner;
     type Setter;
@@ -11,6 +11,30 @@ pub enum QualityOfService {
     ExactlyOnce,
 }
 
+impl QualityOfService {
+    /// The value of the quality of service on the wire, the same for all protocol versions
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            QualityOfService::AtMostOnce => 0,
+            QualityOfService::AtLeastOnce => 1,
+            QualityOfService::ExactlyOnce => 2,
+        }
+    }
+
+    pub fn try_from_u8(value: u8) -> Result<Self, InvalidQualityOfService> {
+        match value {
+            0 => Ok(QualityOfService::AtMostOnce),
+            1 => Ok(QualityOfService::AtLeastOnce),
+            2 => Ok(QualityOfService::ExactlyOnce),
+            other => Err(InvalidQualityOfService(other)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("{} is not a valid quality of service", .0)]
+pub struct InvalidQualityOfService(pub u8);
+
 impl From<QualityOfService> for mqtt_format::v5::qos::QualityOfService {
     fn from(value: QualityOfService) -> Self {
         match value {
@@ -20,3 +44,75 @@ fn from(value: QualityOfService) -> Self {
         }
     }
 }
+
+impl From<mqtt_format::v5::qos::QualityOfService> for QualityOfService {
+    fn from(value: mqtt_format::v5::qos::QualityOfService) -> Self {
+        match value {
+            mqtt_format::v5::qos::QualityOfService::AtMostOnce => QualityOfService::AtMostOnce,
+            mqtt_format::v5::qos::QualityOfService::AtLeastOnce => QualityOfService::AtLeastOnce,
+            mqtt_format::v5::qos::QualityOfService::ExactlyOnce => QualityOfService::ExactlyOnce,
+        }
+    }
+}
+
+impl From<QualityOfService> for mqtt_format::v3::qos::MQualityOfService {
+    fn from(value: QualityOfService) -> Self {
+        match value {
+            QualityOfService::AtMostOnce => mqtt_format::v3::qos::MQualityOfService::AtMostOnce,
+            QualityOfService::AtLeastOnce => mqtt_format::v3::qos::MQualityOfService::AtLeastOnce,
+            QualityOfService::ExactlyOnce => mqtt_format::v3::qos::MQualityOfService::ExactlyOnce,
+        }
+    }
+}
+
+impl From<mqtt_format::v3::qos::MQualityOfService> for QualityOfService {
+    fn from(value: mqtt_format::v3::qos::MQualityOfService) -> Self {
+        match value {
+            mqtt_format::v3::qos::MQualityOfService::AtMostOnce => QualityOfService::AtMostOnce,
+            mqtt_format::v3::qos::MQualityOfService::AtLeastOnce => QualityOfService::AtLeastOnce,
+            mqtt_format::v3::qos::MQualityOfService::ExactlyOnce => QualityOfService::ExactlyOnce,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mqtt_format::v3::qos::MQualityOfService;
+
+    use super::InvalidQualityOfService;
+    use super::QualityOfService;
+
+    const ALL: [QualityOfService; 3] = [
+        QualityOfService::AtMostOnce,
+        QualityOfService::AtLeastOnce,
+        QualityOfService::ExactlyOnce,
+    ];
+
+    #[test]
+    fn converts_to_and_from_both_versions() {
+        for qos in ALL {
+            let v3 = MQualityOfService::from(qos);
+            let v5 = mqtt_format::v5::qos::QualityOfService::from(qos);
+
+            assert_eq!(v3.to_byte(), qos.as_u8());
+            assert_eq!(u8::from(v5), qos.as_u8());
+            assert_eq!(QualityOfService::from(v3), qos);
+            assert_eq!(QualityOfService::from(v5), qos);
+        }
+    }
+
+    #[test]
+    fn u8_roundtrip() {
+        for qos in ALL {
+            assert_eq!(QualityOfService::try_from_u8(qos.as_u8()), Ok(qos));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_value() {
+        assert_eq!(
+            QualityOfService::try_from_u8(3),
+            Err(InvalidQualityOfService(3))
+        );
+    }
+}
@@ -9,17 +9,42 @@
 
 impl MqttString {
     pub const MAX_LEN: usize = u16::MAX as usize;
+
+    /// Create a new MqttString, checking the length and the characters it contains
+    #[doc = crate::util::md_speclink!("_Toc3901010")]
+    pub fn new(s: impl Into<String>) -> Result<Self, MqttStringError> {
+        let s = s.into();
+        Self::validate(&s)?;
+        Ok(Self(s))
+    }
+
+    fn validate(s: &str) -> Result<(), MqttStringError> {
+        if s.len() > Self::MAX_LEN {
+            return Err(MqttStringError::TooLong(s.len()));
+        }
+
+        for (position, c) in s.char_indices() {
+            if c == '\u{0000}' {
+                return Err(MqttStringError::NullCharacter { position });
+            }
+
+            if c.is_control() {
+                return Err(MqttStringError::ControlCharacter {
+                    character: c,
+                    position,
+                });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl std::str::FromStr for MqttString {
     type Err = MqttStringError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() > Self::MAX_LEN {
-            Err(MqttStringError::TooLong(s.len()))
-        } else {
-            Ok(Self(s.to_string()))
-        }
+        Self::new(s)
     }
 }
 
@@ -35,11 +60,7 @@ impl TryFrom<String> for MqttString {
     type Error = MqttStringError;
 
     fn try_from(s: String) -> Result<Self, Self::Error> {
-        if s.len() > Self::MAX_LEN {
-            Err(MqttStringError::TooLong(s.len()))
-        } else {
-            Ok(Self(s.to_string()))
-        }
+        Self::new(s)
     }
 }
 
@@ -59,4 +80,49 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 pub enum MqttStringError {
     #[error("String of length {} is too long, max length is {}", .0, MqttString::MAX_LEN)]
     TooLong(usize),
+
+    #[error("String contains a null character at byte {position}")]
+    NullCharacter { position: usize },
+
+    #[error("String contains the control character {character:?} at byte {position}")]
+    ControlCharacter { character: char, position: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MqttString;
+    use super::MqttStringError;
+
+    #[test]
+    fn accepts_valid_strings() {
+        assert_eq!(MqttString::new("foo/bar").unwrap().as_ref(), "foo/bar");
+        assert!(MqttString::new("a".repeat(MqttString::MAX_LEN)).is_ok());
+    }
+
+    #[test]
+    fn rejects_over_length_strings() {
+        let err = MqttString::new("a".repeat(MqttString::MAX_LEN + 1)).unwrap_err();
+        assert!(matches!(err, MqttStringError::TooLong(len) if len == MqttString::MAX_LEN + 1));
+    }
+
+    #[test]
+    fn rejects_null_characters() {
+        let err = MqttString::new("foo\0bar").unwrap_err();
+        assert!(matches!(
+            err,
+            MqttStringError::NullCharacter { position: 3 }
+        ));
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        let err = MqttString::try_from("foo\u{7f}").unwrap_err();
+        assert!(matches!(
+            err,
+            MqttStringError::ControlCharacter {
+                character: '\u{7f}',
+                position: 3
+            }
+        ));
+    }
 }
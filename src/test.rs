@@ -0,0 +1,111 @@
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this
+//   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+use futures::SinkExt;
+use futures::StreamExt;
+use mqtt_format::v5::packets::connack::ConnackProperties;
+use mqtt_format::v5::packets::connack::ConnackReasonCode;
+use mqtt_format::v5::packets::connack::MConnack;
+use mqtt_format::v5::packets::MqttPacket as FormatMqttPacket;
+use tokio_util::codec::Framed;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use crate::client::connect::CleanStart;
+use crate::client::connect::MqttClientConnector;
+use crate::client::MqttClient;
+use crate::client_identifier::ProposedClientIdentifier;
+use crate::codecs::MqttPacketCodec;
+use crate::keep_alive::KeepAlive;
+use crate::packets::MqttPacket;
+use crate::transport::MqttConnectTransport;
+use crate::transport::MqttConnection;
+
+/// The server side of an in-memory connection, used to play the broker in tests
+pub(crate) struct TestServer {
+    pub(crate) framed: Framed<MqttConnection, MqttPacketCodec>,
+}
+
+pub(crate) fn make_transport() -> (MqttConnectTransport, TestServer) {
+    let (client, server) = tokio::io::duplex(1024);
+
+    let server = TestServer {
+        framed: Framed::new(MqttConnection::Duplex(server.compat()), MqttPacketCodec),
+    };
+
+    (MqttConnectTransport::TokioDuplex(client), server)
+}
+
+pub(crate) fn make_connector(transport: MqttConnectTransport) -> MqttClientConnector {
+    MqttClientConnector::new(
+        transport,
+        ProposedClientIdentifier::new_potetially_accepted("test").unwrap(),
+        CleanStart::Yes,
+        KeepAlive::Disabled,
+    )
+}
+
+/// Connect `client` to a new [`TestServer`] and spawn its background task
+pub(crate) async fn connect(client: &MqttClient) -> TestServer {
+    connect_with(client, ConnackProperties::new()).await
+}
+
+/// Like [`connect`], but the server answers with the given CONNACK properties
+pub(crate) async fn connect_with(
+    client: &MqttClient,
+    properties: ConnackProperties<'_>,
+) -> TestServer {
+    let (transport, mut server) = make_transport();
+
+    let (connected, _) = tokio::join!(
+        client.connect(make_connector(transport)),
+        server.accept_connect_with(properties)
+    );
+    tokio::spawn(connected.unwrap().background_task);
+
+    server
+}
+
+impl TestServer {
+    pub(crate) async fn receive(&mut self) -> MqttPacket {
+        self.framed
+            .next()
+            .await
+            .expect("The client closed the connection")
+            .expect("The client sent an invalid packet")
+    }
+
+    pub(crate) async fn send(&mut self, packet: FormatMqttPacket<'_>) {
+        self.framed.send(packet).await.unwrap();
+    }
+
+    pub(crate) async fn send_bytes(&mut self, bytes: &[u8]) {
+        use tokio::io::AsyncWriteExt;
+
+        self.framed.get_mut().write_all(bytes).await.unwrap();
+    }
+
+    /// Wait for a CONNECT and answer it with a successful CONNACK
+    pub(crate) async fn accept_connect(&mut self) -> MqttPacket {
+        self.accept_connect_with(ConnackProperties::new()).await
+    }
+
+    pub(crate) async fn accept_connect_with(
+        &mut self,
+        properties: ConnackProperties<'_>,
+    ) -> MqttPacket {
+        let connect = self.receive().await;
+        assert!(matches!(connect.get(), FormatMqttPacket::Connect(_)));
+
+        self.send(FormatMqttPacket::Connack(MConnack {
+            session_present: false,
+            reason_code: ConnackReasonCode::Success,
+            properties,
+        }))
+        .await;
+
+        connect
+    }
+}
@@ -13,9 +13,15 @@
 use tokio_util::compat::Compat as TokioCompat;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
+/// A byte stream a client can connect over, see [`MqttConnectTransport::Custom`]
+pub trait AsyncReadWrite: TokioAsyncRead + TokioAsyncWrite {}
+
+impl<T: TokioAsyncRead + TokioAsyncWrite> AsyncReadWrite for T {}
+
 pub(crate) enum MqttConnection {
     Tokio(TokioCompat<tokio::net::TcpStream>),
     Duplex(TokioCompat<tokio::io::DuplexStream>),
+    Custom(TokioCompat<Box<dyn AsyncReadWrite + Send + Unpin>>),
 }
 
 impl TokioAsyncRead for MqttConnection {
@@ -27,6 +33,7 @@ fn poll_read(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t.get_mut()).poll_read(cx, buf),
             MqttConnection::Duplex(d) => std::pin::pin!(d.get_mut()).poll_read(cx, buf),
+            MqttConnection::Custom(c) => std::pin::pin!(c.get_mut()).poll_read(cx, buf),
         }
     }
 }
@@ -40,6 +47,7 @@ fn poll_write(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t.get_mut()).poll_write(cx, buf),
             MqttConnection::Duplex(d) => std::pin::pin!(d.get_mut()).poll_write(cx, buf),
+            MqttConnection::Custom(c) => std::pin::pin!(c.get_mut()).poll_write(cx, buf),
         }
     }
 
@@ -50,6 +58,7 @@ fn poll_flush(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t.get_mut()).poll_flush(cx),
             MqttConnection::Duplex(d) => std::pin::pin!(d.get_mut()).poll_flush(cx),
+            MqttConnection::Custom(c) => std::pin::pin!(c.get_mut()).poll_flush(cx),
         }
     }
 
@@ -60,6 +69,7 @@ fn poll_shutdown(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t.get_mut()).poll_shutdown(cx),
             MqttConnection::Duplex(d) => std::pin::pin!(d.get_mut()).poll_shutdown(cx),
+            MqttConnection::Custom(c) => std::pin::pin!(c.get_mut()).poll_shutdown(cx),
         }
     }
 }
@@ -73,6 +83,7 @@ fn poll_read(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t).poll_read(cx, buf),
             MqttConnection::Duplex(d) => std::pin::pin!(d).poll_read(cx, buf),
+            MqttConnection::Custom(c) => std::pin::pin!(c).poll_read(cx, buf),
         }
     }
 }
@@ -86,6 +97,7 @@ fn poll_write(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t).poll_write(cx, buf),
             MqttConnection::Duplex(d) => std::pin::pin!(d).poll_write(cx, buf),
+            MqttConnection::Custom(c) => std::pin::pin!(c).poll_write(cx, buf),
         }
     }
 
@@ -96,6 +108,7 @@ fn poll_flush(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t).poll_flush(cx),
             MqttConnection::Duplex(d) => std::pin::pin!(d).poll_flush(cx),
+            MqttConnection::Custom(c) => std::pin::pin!(c).poll_flush(cx),
         }
     }
 
@@ -106,6 +119,7 @@ fn poll_close(
         match &mut *self {
             MqttConnection::Tokio(t) => std::pin::pin!(t).poll_close(cx),
             MqttConnection::Duplex(d) => std::pin::pin!(d).poll_close(cx),
+            MqttConnection::Custom(c) => std::pin::pin!(c).poll_close(cx),
         }
     }
 }
@@ -113,6 +127,118 @@ fn poll_close(
 pub enum MqttConnectTransport {
     TokioTcp(TcpStream),
     TokioDuplex(DuplexStream),
+    /// Any other stream, like a pipe or a tunnel, that is already connected to the server
+    Custom(Box<dyn AsyncReadWrite + Send + Unpin>),
+}
+
+/// Which kind of addresses to try first when a host resolves to both IPv4 and IPv6 addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressFamilyPreference {
+    /// Keep the order returned by the resolver
+    #[default]
+    Any,
+    PreferIpv4,
+    PreferIpv6,
+}
+
+/// Socket options applied to a TCP connection before any MQTT packet is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Disable Nagle's algorithm, MQTT packets are usually small and latency sensitive
+    pub nodelay: bool,
+    /// Enable keep alive probes on the socket, independent of the MQTT keep alive
+    pub keepalive: bool,
+    pub send_buffer_size: Option<u32>,
+    pub recv_buffer_size: Option<u32>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: false,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+
+impl TcpOptions {
+    fn socket_for(&self, address: std::net::SocketAddr) -> std::io::Result<tokio::net::TcpSocket> {
+        let socket = if address.is_ipv4() {
+            tokio::net::TcpSocket::new_v4()?
+        } else {
+            tokio::net::TcpSocket::new_v6()?
+        };
+
+        socket.set_keepalive(self.keepalive)?;
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(socket)
+    }
+
+    async fn connect(&self, address: std::net::SocketAddr) -> std::io::Result<TcpStream> {
+        let stream = self.socket_for(address)?.connect(address).await?;
+        stream.set_nodelay(self.nodelay)?;
+
+        Ok(stream)
+    }
+}
+
+impl MqttConnectTransport {
+    /// Connect over an already established stream
+    pub fn from_stream(stream: impl AsyncReadWrite + Send + Unpin + 'static) -> Self {
+        MqttConnectTransport::Custom(Box::new(stream))
+    }
+
+    /// Connect over TCP with the default [`TcpOptions`], see [`Self::connect_tcp_with`]
+    pub async fn connect_tcp(
+        addr: impl tokio::net::ToSocketAddrs + Send,
+        preference: AddressFamilyPreference,
+    ) -> std::io::Result<MqttConnectTransport> {
+        Self::connect_tcp_with(addr, preference, TcpOptions::default()).await
+    }
+
+    /// Connect over TCP, trying every resolved address in turn until one connects
+    ///
+    /// If no address could be connected to, the error of the last attempt is returned.
+    pub async fn connect_tcp_with(
+        addr: impl tokio::net::ToSocketAddrs + Send,
+        preference: AddressFamilyPreference,
+        options: TcpOptions,
+    ) -> std::io::Result<MqttConnectTransport> {
+        let mut addresses = tokio::net::lookup_host(addr).await?.collect::<Vec<_>>();
+
+        // Sorting is stable, so the resolver order is kept within each address family
+        match preference {
+            AddressFamilyPreference::Any => {}
+            AddressFamilyPreference::PreferIpv4 => addresses.sort_by_key(|a| !a.is_ipv4()),
+            AddressFamilyPreference::PreferIpv6 => addresses.sort_by_key(|a| !a.is_ipv6()),
+        }
+
+        let mut last_error = None;
+        for address in addresses {
+            match options.connect(address).await {
+                Ok(stream) => return Ok(MqttConnectTransport::TokioTcp(stream)),
+                Err(error) => {
+                    tracing::debug!(%address, %error, "Could not connect, trying next address");
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "could not resolve to any address",
+            )
+        }))
+    }
 }
 
 impl From<MqttConnectTransport> for MqttConnection {
@@ -120,6 +246,115 @@ fn from(value: MqttConnectTransport) -> Self {
         match value {
             MqttConnectTransport::TokioTcp(t) => MqttConnection::Tokio(t.compat()),
             MqttConnectTransport::TokioDuplex(d) => MqttConnection::Duplex(d.compat()),
+            MqttConnectTransport::Custom(c) => MqttConnection::Custom(c.compat()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::AddressFamilyPreference;
+    use super::MqttConnectTransport;
+    use super::TcpOptions;
+
+    #[tokio::test]
+    async fn connect_tcp_falls_back_to_next_address() {
+        let unreachable = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let reachable = listener.local_addr().unwrap();
+
+        let addresses: &[SocketAddr] = &[unreachable, reachable];
+        let (transport, accepted) = tokio::join!(
+            MqttConnectTransport::connect_tcp(addresses, AddressFamilyPreference::Any),
+            listener.accept()
+        );
+
+        let MqttConnectTransport::TokioTcp(stream) = transport.unwrap() else {
+            panic!("Expected a TCP transport");
+        };
+        assert_eq!(stream.peer_addr().unwrap(), reachable);
+        assert_eq!(accepted.unwrap().1, stream.local_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn connect_tcp_enables_nodelay_by_default() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let (transport, _accepted) = tokio::join!(
+            MqttConnectTransport::connect_tcp(address, AddressFamilyPreference::Any),
+            listener.accept()
+        );
+
+        let MqttConnectTransport::TokioTcp(stream) = transport.unwrap() else {
+            panic!("Expected a TCP transport");
+        };
+        assert!(stream.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn connect_tcp_with_applies_options() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let options = TcpOptions {
+            nodelay: false,
+            ..TcpOptions::default()
+        };
+
+        let (transport, _accepted) = tokio::join!(
+            MqttConnectTransport::connect_tcp_with(address, AddressFamilyPreference::Any, options),
+            listener.accept()
+        );
+
+        let MqttConnectTransport::TokioTcp(stream) = transport.unwrap() else {
+            panic!("Expected a TCP transport");
+        };
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn connect_tcp_reports_last_error() {
+        let unreachable = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let error = MqttConnectTransport::connect_tcp(unreachable, AddressFamilyPreference::Any)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn connects_over_custom_stream() {
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        let (client_stream, server_stream) = tokio::io::duplex(1024);
+        let mut server = crate::test::TestServer {
+            framed: tokio_util::codec::Framed::new(
+                super::MqttConnection::Duplex(server_stream.compat()),
+                crate::codecs::MqttPacketCodec,
+            ),
+        };
+
+        let client = crate::client::MqttClient::new_with_default_handlers();
+        let connector = crate::test::make_connector(MqttConnectTransport::from_stream(
+            tokio::io::BufStream::new(client_stream),
+        ));
+        let (connected, _) = tokio::join!(client.connect(connector), server.accept_connect());
+        let _connected = connected.unwrap();
+
+        assert_eq!(
+            client.connection_state().await,
+            crate::client::connect::ConnectionStatus::Connected
+        );
+    }
+}